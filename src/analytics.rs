@@ -0,0 +1,245 @@
+//! Local computation of the average grades from raw marks
+//!
+//! The portal averages marks weighted by their `weight`, using the five- and hundred-based
+//! values of each mark in its own grading system, so marks of ten- or hundred-based
+//! systems are averaged together with five-based ones.
+
+pub mod attendance;
+pub mod simulator;
+
+use crate::model::lessons::MarkInstance;
+use crate::model::marks::{GlobalAverageGrade, PeriodAverageGrade};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Largest difference between a computed and a server average still considered equal,
+/// as the server rounds the averages to two decimals, which are then read as `f32`
+const TOLERANCE: f64 = 0.01 + 1e-5;
+
+/// Which marks take part in the averages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AverageOptions {
+    /// Whether marks that should be corrected (points) are counted. The portal skips them
+    pub include_points: bool,
+    /// Whether control examination marks are counted
+    pub include_exams: bool,
+}
+
+impl Default for AverageOptions {
+    fn default() -> Self {
+        Self {
+            include_points: false,
+            include_exams: true,
+        }
+    }
+}
+
+impl AverageOptions {
+    fn counts(&self, mark: &MarkInstance) -> bool {
+        (self.include_points || !mark.is_point) && (self.include_exams || !mark.is_exam)
+    }
+}
+
+/// Weighted average of some marks
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Average {
+    pub five: f64,
+    pub hundred: f64,
+    /// Sum of the weights of the counted marks
+    pub weight: f64,
+    /// Amount of the counted marks
+    pub count: usize,
+}
+
+/// Five- and hundred-based values of the mark. Marks without system values fall back to
+/// converting their value, `None` for non-numeric marks
+pub fn mark_values(mark: &MarkInstance) -> Option<(f64, f64)> {
+    match mark.grade() {
+        Some(grade) => Some((grade.five_based.into(), grade.hundred_based.into())),
+        None => {
+            let value = mark.typed_value();
+            Some((value.five_based()?.into(), value.hundred_based()?.into()))
+        }
+    }
+}
+
+/// Weighted average of the marks, `None` if no mark is counted
+pub fn weighted_average<'a, I>(marks: I, options: &AverageOptions) -> Option<Average>
+where
+    I: IntoIterator<Item = &'a MarkInstance>,
+{
+    let mut sum = (0.0, 0.0);
+    let mut weight = 0.0;
+    let mut count = 0;
+    for mark in marks.into_iter().filter(|mark| options.counts(mark)) {
+        let (five, hundred) = match mark_values(mark) {
+            Some(values) => values,
+            None => continue,
+        };
+        let mark_weight = f64::from(mark.weight);
+        sum.0 += five * mark_weight;
+        sum.1 += hundred * mark_weight;
+        weight += mark_weight;
+        count += 1;
+    }
+    (weight > 0.0).then(|| Average {
+        five: sum.0 / weight,
+        hundred: sum.1 / weight,
+        weight,
+        count,
+    })
+}
+
+/// Computed average of a period
+#[derive(Debug, Clone, Serialize)]
+pub struct PeriodAverage {
+    pub name: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub average: Option<Average>,
+}
+
+/// Computed averages of a subject over the whole year and each of its periods
+#[derive(Debug, Clone, Serialize)]
+pub struct SubjectAverage {
+    pub subject_name: String,
+    pub average: Option<Average>,
+    pub periods: Vec<PeriodAverage>,
+}
+
+/// Recomputes the averages of the subject from the marks of its periods
+pub fn subject_average(grade: &GlobalAverageGrade, options: &AverageOptions) -> SubjectAverage {
+    SubjectAverage {
+        subject_name: grade.subject_name.clone(),
+        average: weighted_average(
+            grade.periods.iter().flat_map(|period| &period.marks),
+            options,
+        ),
+        periods: grade
+            .periods
+            .iter()
+            .map(|period| period_average(period, options))
+            .collect(),
+    }
+}
+
+fn period_average(period: &PeriodAverageGrade, options: &AverageOptions) -> PeriodAverage {
+    PeriodAverage {
+        name: period.name.clone(),
+        start: period.start,
+        end: period.end,
+        average: weighted_average(&period.marks, options),
+    }
+}
+
+/// Grading scale of an average
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scale {
+    Five,
+    Hundred,
+}
+
+/// Server average that differs from the one computed from the marks
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Discrepancy {
+    pub subject_name: String,
+    /// Name of the period, `None` for the average of the whole year
+    pub period: Option<String>,
+    pub scale: Scale,
+    /// Average as returned by the server
+    pub server: Option<f32>,
+    pub computed: Option<f64>,
+}
+
+/// Compares the server averages of the progress report with the ones computed from the marks
+pub fn cross_check(progress: &[GlobalAverageGrade], options: &AverageOptions) -> Vec<Discrepancy> {
+    let mut discrepancies = vec![];
+    for grade in progress {
+        let computed = subject_average(grade, options);
+        let mut check = |period: Option<&str>,
+                         average: Option<Average>,
+                         five: Option<f32>,
+                         hundred: Option<f32>| {
+            for (scale, server) in [(Scale::Five, five), (Scale::Hundred, hundred)] {
+                let value = average.map(|average| match scale {
+                    Scale::Five => average.five,
+                    Scale::Hundred => average.hundred,
+                });
+                if !matches(server, value) {
+                    discrepancies.push(Discrepancy {
+                        subject_name: grade.subject_name.clone(),
+                        period: period.map(str::to_string),
+                        scale,
+                        server,
+                        computed: value,
+                    });
+                }
+            }
+        };
+        check(None, computed.average, grade.five, grade.hundred);
+        for (period, average) in grade.periods.iter().zip(&computed.periods) {
+            check(
+                Some(&period.name),
+                average.average,
+                period.five,
+                period.hundred,
+            );
+        }
+    }
+    discrepancies
+}
+
+/// Whether the server average matches the computed one
+fn matches(server: Option<f32>, computed: Option<f64>) -> bool {
+    match (server, computed) {
+        (Some(server), Some(computed)) => (f64::from(server) - computed).abs() <= TOLERANCE,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+
+    fn progress() -> Vec<GlobalAverageGrade> {
+        serde_json::from_value(fixture(
+            "get_jersey_api_progress_json@academic_year_id=10&student_profile_id=1000001.json",
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_weighted_averages() {
+        let mut progress = progress();
+        let options = AverageOptions::default();
+        let algebra = subject_average(&progress[0], &options).average.unwrap();
+        assert_eq!((algebra.five, algebra.hundred), (4.75, 95.0));
+        assert_eq!((algebra.weight, algebra.count), (4.0, 3));
+
+        // the server rounded the hundred-based physics average of the period differently
+        let discrepancies = cross_check(&progress, &options);
+        assert_eq!(discrepancies.len(), 1);
+        let physics = &discrepancies[0];
+        assert_eq!(physics.period.as_deref(), Some("1 четверть"));
+        assert_eq!(
+            (physics.scale, physics.server),
+            (Scale::Hundred, Some(86.6))
+        );
+
+        // points are skipped unless requested
+        progress[0].periods[0].marks[1].is_point = true;
+        let algebra = subject_average(&progress[0], &options).average.unwrap();
+        assert_eq!(algebra.five, 5.0);
+        let all = AverageOptions {
+            include_points: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            subject_average(&progress[0], &all).average.unwrap().five,
+            4.75
+        );
+    }
+}
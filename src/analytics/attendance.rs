@@ -0,0 +1,224 @@
+//! Attendance report from the visits to the school building and the schedules
+//!
+//! Visit times are local, so the lessons are compared with them in the Moscow time zone.
+
+use crate::model::attendance::StudentAttendance;
+use crate::model::lessons::{LessonActivity, Schedule, ScheduleActivity};
+use crate::time::moscow;
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Time spent in school during a week
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekAttendance {
+    /// Monday of the week
+    pub week_start: NaiveDate,
+    /// Days with at least one visit
+    pub days: usize,
+    /// Total time spent inside school, in minutes
+    pub minutes: i64,
+}
+
+/// Day on which the student came after the first lesson began or left before the last one ended
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Deviation {
+    pub date: NaiveDate,
+    /// Local time the first lesson begins or the last lesson ends
+    pub expected: NaiveTime,
+    /// Local time of the entrance or the exit
+    pub actual: NaiveTime,
+    /// Name of the subject of the first or the last lesson
+    pub subject_name: String,
+}
+
+impl Deviation {
+    /// How late the student came or how early they left
+    pub fn difference(&self) -> Duration {
+        (self.expected - self.actual).abs()
+    }
+}
+
+/// Lessons of a subject the student missed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissedLessons {
+    pub subject_name: String,
+    /// Dates of the missed lessons, one per lesson
+    pub dates: Vec<NaiveDate>,
+}
+
+/// Attendance over the provided visits and schedules
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AttendanceReport {
+    pub weeks: Vec<WeekAttendance>,
+    pub late_arrivals: Vec<Deviation>,
+    pub early_departures: Vec<Deviation>,
+    /// Missed lessons by subject, sorted by the subject name
+    pub missed_lessons: Vec<MissedLessons>,
+    /// Days with a visit marked with a warning
+    pub warnings: Vec<NaiveDate>,
+}
+
+impl AttendanceReport {
+    /// Total amount of missed lessons
+    pub fn missed_count(&self) -> usize {
+        self.missed_lessons
+            .iter()
+            .map(|missed| missed.dates.len())
+            .sum()
+    }
+}
+
+/// Builds the attendance report. Cancelled lessons are ignored, and days without a schedule
+/// only count towards the time spent in school
+pub fn attendance_report(
+    attendance: &[StudentAttendance],
+    schedules: &[Schedule],
+) -> AttendanceReport {
+    let mut report = AttendanceReport::default();
+    let mut weeks: BTreeMap<NaiveDate, WeekAttendance> = BTreeMap::new();
+    for day in attendance {
+        if day.visits.is_empty() {
+            continue;
+        }
+        let week_start =
+            day.date - Duration::days(day.date.weekday().num_days_from_monday().into());
+        let week = weeks.entry(week_start).or_insert(WeekAttendance {
+            week_start,
+            days: 0,
+            minutes: 0,
+        });
+        week.days += 1;
+        week.minutes += day
+            .visits
+            .iter()
+            .filter_map(|visit| visit.time_spent())
+            .map(|spent| spent.num_minutes())
+            .sum::<i64>();
+        if day.visits.iter().any(|visit| visit.is_warning) {
+            report.warnings.push(day.date);
+        }
+
+        let lessons = match schedules.iter().find(|schedule| schedule.date == day.date) {
+            Some(schedule) => lessons(schedule),
+            None => continue,
+        };
+        let entrance = day.visits.iter().map(|visit| visit.entrance).min();
+        if let (Some(first), Some(entrance)) = (lessons.first(), entrance) {
+            let begin = first.begin.with_timezone(&moscow()).time();
+            if entrance > begin {
+                report.late_arrivals.push(Deviation {
+                    date: day.date,
+                    expected: begin,
+                    actual: entrance,
+                    subject_name: first.subject.subject_name.clone(),
+                });
+            }
+        }
+        // the exit is unknown while any of the visits is still going on
+        let exit = day
+            .visits
+            .iter()
+            .map(|visit| visit.exit)
+            .collect::<Option<Vec<_>>>()
+            .and_then(|exits| exits.into_iter().max());
+        if let (Some(last), Some(exit)) = (lessons.last(), exit) {
+            let end = last.end.with_timezone(&moscow()).time();
+            if exit < end {
+                report.early_departures.push(Deviation {
+                    date: day.date,
+                    expected: end,
+                    actual: exit,
+                    subject_name: last.subject.subject_name.clone(),
+                });
+            }
+        }
+    }
+    report.weeks = weeks.into_values().collect();
+
+    let mut missed: BTreeMap<&str, Vec<NaiveDate>> = BTreeMap::new();
+    for schedule in schedules {
+        for lesson in lessons(schedule) {
+            if lesson.subject.is_missed_lesson {
+                missed
+                    .entry(&lesson.subject.subject_name)
+                    .or_default()
+                    .push(schedule.date);
+            }
+        }
+    }
+    report.missed_lessons = missed
+        .into_iter()
+        .map(|(subject_name, mut dates)| {
+            dates.sort();
+            MissedLessons {
+                subject_name: subject_name.to_string(),
+                dates,
+            }
+        })
+        .collect();
+    report
+}
+
+/// Lessons of the day that were not cancelled, ordered by their beginning
+fn lessons(schedule: &Schedule) -> Vec<&LessonActivity> {
+    let mut lessons: Vec<&LessonActivity> = schedule
+        .lessons
+        .iter()
+        .filter_map(|activity| match activity {
+            ScheduleActivity::Lesson(lesson) if !lesson.subject.is_cancelled => Some(&**lesson),
+            _ => None,
+        })
+        .collect();
+    lessons.sort_by_key(|lesson| lesson.begin);
+    lessons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::attendance::Payload;
+    use crate::testing::fixture;
+
+    #[test]
+    fn test_attendance_report() {
+        let visits: Payload<Vec<StudentAttendance>> = serde_json::from_value(fixture(
+            "get_mobile_api_visits@contract_id=2000001&from=2022-10-05&to=2022-10-12.json",
+        ))
+        .unwrap();
+        let mut attendance = visits.payload;
+        let mut schedule: Schedule = serde_json::from_value(fixture(
+            "get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json",
+        ))
+        .unwrap();
+        // the first lesson begins at 8:30 and the last one that is not cancelled ends at 11:05
+        let wednesday = attendance.last_mut().unwrap();
+        wednesday.visits[0].entrance = NaiveTime::from_hms_opt(8, 40, 0).unwrap();
+        wednesday.visits[0].exit = NaiveTime::from_hms_opt(10, 50, 0);
+        if let ScheduleActivity::Lesson(lesson) = &mut schedule.lessons[2] {
+            lesson.subject.is_missed_lesson = true;
+        }
+
+        let report = attendance_report(&attendance, &[schedule]);
+        let minutes: Vec<(u32, usize, i64)> = report
+            .weeks
+            .iter()
+            .map(|week| (week.week_start.day(), week.days, week.minutes))
+            .collect();
+        // the visit of 11.10 is not over yet
+        assert_eq!(minutes, [(3, 3, 345 + 276 + 224), (10, 3, 375 + 218)]);
+        assert_eq!(report.late_arrivals.len(), 1);
+        assert_eq!(report.late_arrivals[0].difference(), Duration::minutes(10));
+        assert_eq!(report.early_departures[0].subject_name, "Физика");
+        assert_eq!(
+            report.early_departures[0].difference(),
+            Duration::minutes(15)
+        );
+        assert_eq!(report.missed_lessons[0].subject_name, "Русский язык");
+        assert_eq!(report.missed_count(), 1);
+        assert_eq!(
+            report.warnings,
+            [NaiveDate::from_ymd_opt(2022, 10, 11).unwrap()]
+        );
+    }
+}
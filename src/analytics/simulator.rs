@@ -0,0 +1,191 @@
+//! "What-if" planning of the period and year averages with hypothetical future marks
+
+use super::{weighted_average, Average, AverageOptions};
+use crate::model::marks::{GlobalAverageGrade, PeriodAverageGrade};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Values a mark can have on the five-based scale, from the best one
+const MARK_VALUES: [f64; 4] = [5.0, 4.0, 3.0, 2.0];
+
+/// Mark that might be received in the future
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HypotheticalMark {
+    /// Five-based value of the mark
+    pub value: f64,
+    pub weight: f64,
+}
+
+impl HypotheticalMark {
+    pub fn new(value: f64, weight: f64) -> Self {
+        Self { value, weight }
+    }
+}
+
+/// Averages of a subject before and after receiving the hypothetical marks
+#[derive(Debug, Clone, Serialize)]
+pub struct Simulation {
+    pub subject_name: String,
+    /// Name of the period the marks were added to
+    pub period: String,
+    pub period_before: Option<Average>,
+    pub period_after: Option<Average>,
+    pub year_before: Option<Average>,
+    pub year_after: Option<Average>,
+}
+
+/// Simulates marks of a subject from the progress report
+#[derive(Debug, Clone)]
+pub struct Simulator<'a> {
+    grade: &'a GlobalAverageGrade,
+    options: AverageOptions,
+}
+
+impl<'a> Simulator<'a> {
+    pub fn new(grade: &'a GlobalAverageGrade) -> Self {
+        Self {
+            grade,
+            options: AverageOptions::default(),
+        }
+    }
+
+    /// Sets which of the existing marks take part in the averages
+    pub fn options(mut self, options: AverageOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Index of the period containing the day, or of the last period that started before it
+    pub fn period_at(&self, day: NaiveDate) -> Option<usize> {
+        self.grade
+            .periods
+            .iter()
+            .rposition(|period| period.start <= day)
+    }
+
+    fn period_average(&self, period: &PeriodAverageGrade) -> Option<Average> {
+        weighted_average(&period.marks, &self.options)
+    }
+
+    fn year_average(&self) -> Option<Average> {
+        weighted_average(
+            self.grade.periods.iter().flat_map(|period| &period.marks),
+            &self.options,
+        )
+    }
+
+    /// Recomputes the averages as if the marks were received in the period with the provided
+    /// index. `None` if there is no such period
+    pub fn simulate(&self, period: usize, marks: &[HypotheticalMark]) -> Option<Simulation> {
+        let period = self.grade.periods.get(period)?;
+        let period_before = self.period_average(period);
+        let year_before = self.year_average();
+        Some(Simulation {
+            subject_name: self.grade.subject_name.clone(),
+            period: period.name.clone(),
+            period_before,
+            period_after: with_marks(period_before, marks),
+            year_before,
+            year_after: with_marks(year_before, marks),
+        })
+    }
+
+    /// Fewest marks of the provided weight that bring the five-based average of the period
+    /// to at least `target`, e.g. `4.5` for a five. Among sets of the same size, the one with
+    /// the lowest marks is returned. `None` if more than `max_marks` marks would be needed,
+    /// or if there is no period with the provided index
+    pub fn solve(
+        &self,
+        period: usize,
+        target: f64,
+        weight: f64,
+        max_marks: usize,
+    ) -> Option<Vec<HypotheticalMark>> {
+        let current = self.period_average(self.grade.periods.get(period)?);
+        let reaches = |marks: &[HypotheticalMark]| {
+            with_marks(current, marks).is_some_and(|average| average.five >= target - 1e-9)
+        };
+        for count in 0..=max_marks {
+            let mut marks = vec![HypotheticalMark::new(MARK_VALUES[0], weight); count];
+            if !reaches(&marks) {
+                continue;
+            }
+            // lower the marks one by one while the target is still reached
+            for index in (0..count).rev() {
+                for value in &MARK_VALUES[1..] {
+                    let previous = marks[index].value;
+                    marks[index].value = *value;
+                    if !reaches(&marks) {
+                        marks[index].value = previous;
+                        break;
+                    }
+                }
+            }
+            return Some(marks);
+        }
+        None
+    }
+}
+
+/// Average after adding the marks to the ones counted in `base`
+fn with_marks(base: Option<Average>, marks: &[HypotheticalMark]) -> Option<Average> {
+    let mut average = base.unwrap_or(Average {
+        five: 0.0,
+        hundred: 0.0,
+        weight: 0.0,
+        count: 0,
+    });
+    let mut five = average.five * average.weight;
+    let mut hundred = average.hundred * average.weight;
+    for mark in marks {
+        five += mark.value * mark.weight;
+        hundred += mark.value * 20.0 * mark.weight;
+        average.weight += mark.weight;
+        average.count += 1;
+    }
+    (average.weight > 0.0).then(|| Average {
+        five: five / average.weight,
+        hundred: hundred / average.weight,
+        ..average
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+
+    #[test]
+    fn test_what_if() {
+        let mut progress: Vec<GlobalAverageGrade> = serde_json::from_value(fixture(
+            "get_jersey_api_progress_json@academic_year_id=10&student_profile_id=1000001.json",
+        ))
+        .unwrap();
+        // physics: 4, 5, 4 with the weight of 1
+        let physics = Simulator::new(&progress[1]);
+        let period = physics
+            .period_at(NaiveDate::from_ymd_opt(2022, 10, 12).unwrap())
+            .unwrap();
+
+        let simulation = physics
+            .simulate(period, &[HypotheticalMark::new(5.0, 2.0)])
+            .unwrap();
+        assert_eq!(simulation.period_after.unwrap().five, 4.6);
+        assert_eq!(simulation.year_after.unwrap().count, 4);
+
+        let five = HypotheticalMark::new(5.0, 1.0);
+        assert_eq!(physics.solve(period, 4.5, 1.0, 10).unwrap(), [five]);
+        assert_eq!(physics.solve(period, 4.6, 1.0, 10).unwrap(), [five, five]);
+        assert!(physics.solve(period, 4.0, 1.0, 10).unwrap().is_empty());
+        assert!(physics.solve(period, 5.0, 1.0, 10).is_none());
+        let unknown = physics.grade.periods.len();
+        assert!(physics.simulate(unknown, &[five]).is_none());
+        assert!(physics.solve(unknown, 4.0, 1.0, 10).is_none());
+
+        // without any marks yet a four is enough for 3.5
+        progress[1].periods[period].marks.clear();
+        let empty = Simulator::new(&progress[1]);
+        let marks = empty.solve(period, 3.5, 1.0, 10).unwrap();
+        assert_eq!(marks, [HypotheticalMark::new(4.0, 1.0)]);
+    }
+}
@@ -0,0 +1,381 @@
+//! Obtaining auth tokens from the mos.ru credentials
+
+use crate::client::parse_url;
+use crate::diary::Diary;
+use crate::error::{DnevnikError, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::LOCATION;
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Response};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Auth token for the diary API along with its expiry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthToken {
+    /// Value of the token
+    pub token: String,
+    /// Time at which this token expires. `None` when the token does not carry it
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl AuthToken {
+    /// Creates a token, reading its expiry from the JWT `exp` claim when possible
+    pub fn new<S: Into<String>>(token: S) -> Self {
+        let token = token.into();
+        let expires_at = jwt_expiry(&token);
+        Self { token, expires_at }
+    }
+
+    /// Whether this token has already expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map(|expiry| expiry <= Utc::now())
+            .unwrap_or(false)
+    }
+
+    /// Creates a [Diary] authorized with this token
+    pub async fn into_diary(self) -> Result<Diary> {
+        Diary::new(self.token).await
+    }
+}
+
+impl From<AuthToken> for String {
+    fn from(token: AuthToken) -> Self {
+        token.token
+    }
+}
+
+/// Second-factor challenge issued by the login page
+#[derive(Debug, Clone)]
+pub struct OtpChallenge {
+    /// URL of the page that requested the code
+    pub url: String,
+}
+
+/// Provides one-time codes (usually sent by SMS) for the second authentication factor
+#[async_trait]
+pub trait OtpHandler: Send + Sync {
+    async fn code(&self, challenge: &OtpChallenge) -> Result<String>;
+}
+
+#[async_trait]
+impl<F, Fut> OtpHandler for F
+where
+    F: Fn(OtpChallenge) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String>> + Send,
+{
+    async fn code(&self, challenge: &OtpChallenge) -> Result<String> {
+        self(challenge.clone()).await
+    }
+}
+
+/// URLs and names used during the login flow
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// URL that starts the OAuth flow and redirects to the login page
+    pub start_url: String,
+    /// URL to which login and password are submitted
+    pub password_url: String,
+    /// URL to which the one-time code is submitted
+    pub otp_url: String,
+    /// Text present on the page that asks for the one-time code
+    pub otp_marker: String,
+    /// URL for which the token cookie is set at the end of the flow
+    pub token_url: String,
+    /// Name of the cookie containing the token
+    pub token_cookie: String,
+    /// Maximum amount of redirects followed after a single request
+    pub max_redirects: usize,
+    /// Timeout for a single request
+    pub timeout: Duration,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            start_url: "https://school.mos.ru/v3/auth/sudir/login".to_string(),
+            password_url: "https://login.mos.ru/sps/login/methods/password".to_string(),
+            otp_url: "https://login.mos.ru/sps/login/methods/sms".to_string(),
+            otp_marker: "sms-code".to_string(),
+            token_url: "https://school.mos.ru/".to_string(),
+            token_cookie: "aupd_token".to_string(),
+            max_redirects: 20,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Performs the mos.ru SSO login flow and obtains the auth token
+pub struct Authenticator {
+    config: AuthConfig,
+    otp: Option<Arc<dyn OtpHandler>>,
+}
+
+impl Authenticator {
+    pub fn new() -> Result<Self> {
+        Self::with_config(AuthConfig::default())
+    }
+
+    pub fn with_config(config: AuthConfig) -> Result<Self> {
+        // fail early if the HTTP client can't be built with this configuration
+        LoginSession::new(&config)?;
+        Ok(Self { config, otp: None })
+    }
+
+    /// Sets the handler asked for one-time codes when the account has a second factor enabled
+    pub fn otp_handler<H: OtpHandler + 'static>(mut self, handler: H) -> Self {
+        self.otp = Some(Arc::new(handler));
+        self
+    }
+
+    /// Logs in with the provided credentials and returns the auth token.
+    /// Every login starts with empty cookies, so it never returns a token of a previous login
+    pub async fn login(&self, login: &str, password: &str) -> Result<AuthToken> {
+        let session = LoginSession::new(&self.config)?;
+        let start = parse_url(&self.config.start_url)?;
+        session
+            .follow(session.send(session.client.get(start)).await?)
+            .await?;
+
+        let response = session
+            .follow(
+                session
+                    .send(
+                        session
+                            .client
+                            .post(parse_url(&self.config.password_url)?)
+                            .form(&[
+                                ("login", login),
+                                ("password", password),
+                                ("isDelayed", "false"),
+                            ]),
+                    )
+                    .await?,
+            )
+            .await?;
+        if let Some(token) = session.token()? {
+            return Ok(token);
+        }
+
+        let url = response.url().to_string();
+        let page = response
+            .text()
+            .await
+            .map_err(|e| DnevnikError::from_request(&url, e))?;
+        if !page.contains(&self.config.otp_marker) {
+            return Err(authentication("invalid login or password"));
+        }
+        let handler = self
+            .otp
+            .as_ref()
+            .ok_or_else(|| authentication("one-time code is required, but no handler is set"))?;
+        let code = handler.code(&OtpChallenge { url }).await?;
+        session
+            .follow(
+                session
+                    .send(
+                        session
+                            .client
+                            .post(parse_url(&self.config.otp_url)?)
+                            .form(&[("sms-code", code.as_str())]),
+                    )
+                    .await?,
+            )
+            .await?;
+        session
+            .token()?
+            .ok_or_else(|| authentication("invalid one-time code"))
+    }
+}
+
+/// HTTP client and cookies of a single login
+struct LoginSession<'a> {
+    client: Client,
+    jar: Arc<Jar>,
+    config: &'a AuthConfig,
+}
+
+impl<'a> LoginSession<'a> {
+    fn new(config: &'a AuthConfig) -> Result<Self> {
+        let jar = Arc::new(Jar::default());
+        let client = ClientBuilder::new()
+            .cookie_provider(jar.clone())
+            .redirect(Policy::none())
+            .timeout(config.timeout)
+            .build()
+            .map_err(DnevnikError::Client)?;
+        Ok(Self {
+            client,
+            jar,
+            config,
+        })
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Response> {
+        request
+            .send()
+            .await
+            .map_err(|e| DnevnikError::from_request("login", e))
+    }
+
+    /// Follows redirects manually, so cookies are collected on every hop
+    async fn follow(&self, mut response: Response) -> Result<Response> {
+        for _ in 0..self.config.max_redirects {
+            if !response.status().is_redirection() {
+                return Ok(response);
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .ok_or_else(|| authentication("redirect without a location"))?;
+            let next = response
+                .url()
+                .join(location)
+                .map_err(|e| DnevnikError::InvalidUrl {
+                    url: location.to_string(),
+                    reason: e.to_string(),
+                })?;
+            response = self.send(self.client.get(next)).await?;
+        }
+        Err(authentication("too many redirects"))
+    }
+
+    fn token(&self) -> Result<Option<AuthToken>> {
+        let url = parse_url(&self.config.token_url)?;
+        let cookies = match self.jar.cookies(&url) {
+            Some(cookies) => cookies,
+            None => return Ok(None),
+        };
+        let token = cookies
+            .to_str()
+            .unwrap_or_default()
+            .split(';')
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(name, _)| *name == self.config.token_cookie)
+            .map(|(_, value)| AuthToken::new(value));
+        Ok(token)
+    }
+}
+
+fn authentication(reason: &str) -> DnevnikError {
+    DnevnikError::Authentication {
+        reason: reason.to_string(),
+    }
+}
+
+/// Reads the `exp` claim of a JWT without verifying the signature
+fn jwt_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let decoded = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&decoded).ok()?;
+    Utc.timestamp_opt(claims.get("exp")?.as_i64()?, 0).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockResponse, MockServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn jwt(exp: i64) -> String {
+        format!(
+            "{}.{}.signature",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(format!(r#"{{"exp":{}}}"#, exp))
+        )
+    }
+
+    async fn sso_server() -> MockServer {
+        MockServer::start(|req| {
+            let has_session = req
+                .header("cookie")
+                .map(|c| c.contains("sso_session=1"))
+                .unwrap_or(false);
+            match (req.method.as_str(), req.path()) {
+                ("GET", "/start") => {
+                    MockResponse::redirect("/login").header("Set-Cookie", "sso_session=1; Path=/")
+                }
+                ("GET", "/login") => MockResponse::new(200, "<form>login</form>"),
+                ("POST", "/password") if has_session => {
+                    if req.body_str().contains("login=student&password=secret") {
+                        MockResponse::new(200, r#"<input name="sms-code">"#)
+                    } else {
+                        MockResponse::new(200, "<form>login</form>")
+                    }
+                }
+                ("POST", "/sms") if has_session && req.body_str() == "sms-code=1234" => {
+                    MockResponse::redirect("/callback?code=abc")
+                }
+                ("GET", "/callback") if req.target.ends_with("code=abc") => {
+                    MockResponse::redirect("/diary/").header(
+                        "Set-Cookie",
+                        &format!("aupd_token={}; Path=/", jwt(1_900_000_000)),
+                    )
+                }
+                ("GET", "/diary/") => MockResponse::new(200, "diary"),
+                _ => MockResponse::new(404, "not found"),
+            }
+        })
+        .await
+    }
+
+    fn config(server: &MockServer) -> AuthConfig {
+        AuthConfig {
+            start_url: server.url("/start"),
+            password_url: server.url("/password"),
+            otp_url: server.url("/sms"),
+            token_url: server.url("/"),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_with_otp() -> anyhow::Result<()> {
+        let server = sso_server().await;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let auth = Authenticator::with_config(config(&server))?.otp_handler(
+            move |_challenge: OtpChallenge| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok("1234".to_string())
+                }
+            },
+        );
+        let token = auth.login("student", "secret").await?;
+        assert_eq!(token.token, jwt(1_900_000_000));
+        assert_eq!(token.expires_at.map(|e| e.timestamp()), Some(1_900_000_000));
+        assert!(!token.is_expired());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(server.requests().iter().any(|req| req.path() == "/sms"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_invalid_password() -> anyhow::Result<()> {
+        let server = sso_server().await;
+        let auth = Authenticator::with_config(config(&server))?;
+        let err = auth.login("student", "wrong").await.unwrap_err();
+        assert!(matches!(err, DnevnikError::Authentication { .. }));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_login_does_not_reuse_token() -> anyhow::Result<()> {
+        let server = sso_server().await;
+        let auth = Authenticator::with_config(config(&server))?
+            .otp_handler(|_challenge: OtpChallenge| async { Ok("1234".to_string()) });
+        auth.login("student", "secret").await?;
+        let err = auth.login("student", "wrong").await.unwrap_err();
+        assert!(matches!(err, DnevnikError::Authentication { .. }));
+        Ok(())
+    }
+}
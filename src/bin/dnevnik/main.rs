@@ -0,0 +1,351 @@
+//! Command-line client for the diary
+//!
+//! The auth token is taken from `--token`, the `DNEVNIK_TOKEN` environment variable
+//! or the `token` key of the config file, in that order.
+
+mod output;
+mod settings;
+#[cfg(feature = "tui")]
+mod tui;
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use dnevnik::model::lessons::ScheduleActivity;
+use dnevnik::prelude::*;
+use output::LessonMark;
+use settings::Settings;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+type CliResult<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "dnevnik",
+    version,
+    about = "Command-line client for dnevnik.mos.ru"
+)]
+struct Cli {
+    /// Auth token of the account
+    #[arg(long, env = "DNEVNIK_TOKEN", hide_env_values = true, global = true)]
+    token: Option<String>,
+    /// Config file, `dnevnik/config.toml` in the user's config directory by default
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// ID of the child to use for representative accounts
+    #[arg(long, global = true)]
+    child: Option<u64>,
+    /// Print the data as JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Account and students bound to it
+    Profile,
+    /// Lessons of a day
+    Schedule {
+        /// Day of the schedule, today by default
+        #[arg(long)]
+        date: Option<NaiveDate>,
+        /// Show the whole week of the day
+        #[arg(long)]
+        week: bool,
+    },
+    /// Homework for the period, the next week by default
+    Homework {
+        #[command(flatten)]
+        period: Period,
+    },
+    /// Marks given during the period, the last two weeks by default
+    Marks {
+        #[command(flatten)]
+        period: Period,
+    },
+    /// Average grades of the current academic year
+    Progress,
+    /// Year marks of an academic year
+    FinalMarks {
+        /// ID of the academic year, the previous year by default
+        #[arg(long)]
+        year: Option<u16>,
+    },
+    /// School building visits during the period, the last week by default
+    Visits {
+        #[command(flatten)]
+        period: Period,
+    },
+    /// Lesson plan of a scheduled lesson
+    Plan {
+        /// Schedule item ID of the lesson
+        schedule_id: u64,
+    },
+    /// Downloads the homework attachments of the period, the next week by default
+    DownloadAttachments {
+        /// Directory to save the files to
+        #[arg(long)]
+        dir: PathBuf,
+        #[command(flatten)]
+        period: Period,
+    },
+    /// Exports the schedule of the period as an iCalendar file, the next two weeks by default
+    Ics {
+        /// File to write the calendar to, the standard output by default
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        period: Period,
+    },
+    /// Full-screen viewer of the schedule, marks, homework and attendance
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Directory to save the homework attachments to
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Debug, Args)]
+struct Period {
+    /// First day of the period
+    #[arg(long)]
+    from: Option<NaiveDate>,
+    /// Last day of the period
+    #[arg(long)]
+    to: Option<NaiveDate>,
+}
+
+impl Period {
+    /// Bounds of the period, `days_back` before and `days_ahead` after today unless provided
+    fn range(&self, days_back: i64, days_ahead: i64) -> (DateTime<Utc>, DateTime<Utc>) {
+        let today = Local::now().date_naive();
+        let from = self.from.unwrap_or(today - Duration::days(days_back));
+        let to = self.to.unwrap_or(today + Duration::days(days_ahead));
+        (at_noon(from), at_noon(to))
+    }
+}
+
+/// Middle of the day, so that the date is the same in UTC and in Moscow time
+fn at_noon(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(12, 0, 0).unwrap().and_utc()
+}
+
+/// Path in `dir` to save the attachment to. The file name comes from the server, so only
+/// its last component is kept to stay inside `dir`
+fn attachment_path(dir: &Path, attachment: &HomeworkAttachment) -> PathBuf {
+    let name = attachment
+        .file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..");
+    match name {
+        Some(name) => dir.join(format!("{}-{}", attachment.id, name)),
+        None => dir.join(attachment.id.to_string()),
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn connect(cli: &Cli) -> CliResult<Diary> {
+    let settings = Settings::load(cli.config.as_deref())?;
+    let token = cli.token.clone().or(settings.token).ok_or(
+        "no auth token: pass --token, set DNEVNIK_TOKEN or add `token` to the config file",
+    )?;
+    let mut builder = Diary::builder(token);
+    if let Some(base_url) = settings.base_url {
+        builder = builder.base_url(base_url);
+    }
+    if let Some(child) = cli.child.or(settings.child) {
+        builder = builder.child(child);
+    }
+    Ok(builder.build().await?)
+}
+
+async fn run(cli: Cli) -> CliResult<()> {
+    let diary = connect(&cli).await?;
+    match &cli.command {
+        Command::Profile => {
+            if cli.json {
+                output::json(&diary.profile)?;
+            } else {
+                output::profile(&diary.profile);
+            }
+        }
+        Command::Schedule { date, week } => {
+            let date = date.unwrap_or_else(|| Local::now().date_naive());
+            let schedules = if *week {
+                let monday = date - Duration::days(date.weekday().num_days_from_monday().into());
+                diary
+                    .schedule_range(
+                        at_noon(monday),
+                        at_noon(monday + Duration::days(6)),
+                        RangeOptions::default(),
+                    )
+                    .await
+                    .into_iter()
+                    .map(|(_, schedule)| schedule)
+                    .collect::<dnevnik::error::Result<Vec<_>>>()?
+            } else {
+                vec![diary.schedule(at_noon(date)).await?]
+            };
+            if cli.json {
+                output::json(&schedules)?;
+            } else {
+                output::schedules(&schedules);
+            }
+        }
+        Command::Homework { period } => {
+            let (from, to) = period.range(0, 7);
+            let homeworks = diary.homework(from, to).await?;
+            if cli.json {
+                output::json(&homeworks)?;
+            } else {
+                output::homework(&homeworks);
+            }
+        }
+        Command::Marks { period } => {
+            let (from, to) = period.range(14, 0);
+            let mut schedules = vec![];
+            for (_, schedule) in diary
+                .schedule_range(from, to, RangeOptions::default())
+                .await
+            {
+                schedules.push(schedule?);
+            }
+            let marks: Vec<LessonMark> = schedules
+                .iter()
+                .flat_map(|schedule| {
+                    schedule
+                        .lessons
+                        .iter()
+                        .filter_map(move |activity| match activity {
+                            ScheduleActivity::Lesson(lesson) => Some((schedule.date, lesson)),
+                            ScheduleActivity::Break(_) => None,
+                        })
+                })
+                .flat_map(|(date, lesson)| {
+                    lesson.subject.marks.iter().map(move |mark| LessonMark {
+                        date,
+                        subject_name: &lesson.subject.subject_name,
+                        mark,
+                    })
+                })
+                .collect();
+            if cli.json {
+                output::json(&marks)?;
+            } else {
+                output::marks(&marks);
+            }
+        }
+        Command::Progress => {
+            let grades = diary.progress().await?;
+            if cli.json {
+                output::json(&grades)?;
+            } else {
+                output::progress(&grades);
+            }
+        }
+        Command::FinalMarks { year } => {
+            let year = match year {
+                Some(year) => *year,
+                None => {
+                    let years = diary.academic_years().await?;
+                    years
+                        .iter()
+                        .filter(|year| !year.is_current)
+                        .max_by_key(|year| year.begin_date)
+                        .ok_or("no previous academic year, pass --year")?
+                        .id
+                }
+            };
+            let marks = diary.final_marks_id(year).await?;
+            if cli.json {
+                output::json(&marks)?;
+            } else {
+                output::final_marks(&marks);
+            }
+        }
+        Command::Visits { period } => {
+            let (from, to) = period.range(7, 0);
+            let visits = diary.visits(from, to).await?;
+            if cli.json {
+                output::json(&visits)?;
+            } else {
+                output::visits(&visits);
+            }
+        }
+        Command::Plan { schedule_id } => {
+            let plan = diary.lesson_plan_for(*schedule_id).await?;
+            if cli.json {
+                output::json(&plan)?;
+            } else {
+                output::plan(&plan);
+            }
+        }
+        Command::DownloadAttachments { dir, period } => {
+            let (from, to) = period.range(0, 7);
+            tokio::fs::create_dir_all(dir).await?;
+            let mut paths = vec![];
+            for homework in diary.homework(from, to).await? {
+                for attachment in &homework.homework_entry.attachments {
+                    let path = attachment_path(dir, attachment);
+                    diary.download_attachment(path.clone(), attachment).await?;
+                    paths.push(path);
+                }
+            }
+            if cli.json {
+                output::json(&paths)?;
+            } else {
+                output::downloads(&paths);
+            }
+        }
+        Command::Ics { output, period } => {
+            let (from, to) = period.range(0, 14);
+            let mut schedules = vec![];
+            for (_, schedule) in diary
+                .schedule_range(from, to, RangeOptions::default())
+                .await
+            {
+                schedules.push(schedule?);
+            }
+            let calendar = CalendarExport::new().render(&schedules);
+            match output {
+                Some(path) => tokio::fs::write(path, calendar).await?,
+                None => print!("{}", calendar),
+            }
+        }
+        #[cfg(feature = "tui")]
+        Command::Tui { dir } => tui::run(diary, dir.clone()).await?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_path() {
+        let mut attachment: HomeworkAttachment = serde_json::from_value(serde_json::json!({
+            "id": 6001, "created_at": "10.10.2022 15:18", "file_file_name": "../x",
+            "file_file_size": 93, "file_content_type": "text/plain", "path": "/x"
+        }))
+        .unwrap();
+        let dir = Path::new("downloads");
+        assert_eq!(attachment_path(dir, &attachment), dir.join("6001-x"));
+        attachment.file_name = "..\\..\\.bashrc".to_string();
+        assert_eq!(attachment_path(dir, &attachment), dir.join("6001-.bashrc"));
+        attachment.file_name = "/tmp/..".to_string();
+        assert_eq!(attachment_path(dir, &attachment), dir.join("6001"));
+    }
+}
@@ -0,0 +1,212 @@
+//! Rendering of the diary data as tables
+
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+use comfy_table::{ContentArrangement, Table};
+use dnevnik::model::attendance::format_duration;
+use dnevnik::model::lessons::{FinalMark, LessonPlan, MarkInstance, Schedule, ScheduleActivity};
+use dnevnik::prelude::*;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Prints the value as pretty JSON
+pub fn json<T: Serialize>(value: &T) -> serde_json::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+fn table<const N: usize>(header: [&str; N]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL_CONDENSED)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(header);
+    table
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+pub fn profile(profile: &StudentProfile) {
+    let account = &profile.account;
+    let kind = if profile.is_teacher() {
+        "teacher"
+    } else if profile.is_representative() {
+        "representative"
+    } else {
+        "student"
+    };
+    println!(
+        "{} {} {} ({}, ID {})",
+        account.last_name, account.first_name, account.middle_name, kind, account.id
+    );
+    let mut table = table(["ID", "Name", "Class", "School"]);
+    for child in profile.children() {
+        let student = &child.parent_account;
+        table.add_row(vec![
+            student.id.to_string(),
+            format!("{} {}", student.last_name, student.first_name),
+            child.class_name.clone(),
+            child.school.short_name.clone(),
+        ]);
+    }
+    println!("{table}");
+}
+
+pub fn schedules(schedules: &[Schedule]) {
+    for schedule in schedules {
+        println!(
+            "{} — {}",
+            schedule.date.format("%a %d.%m.%Y"),
+            schedule.summary
+        );
+        let mut table = table(["Time", "Subject", "Room", "Teacher", "Marks", "Homework"]);
+        for activity in &schedule.lessons {
+            let lesson = match activity {
+                ScheduleActivity::Lesson(lesson) => lesson,
+                ScheduleActivity::Break(_) => continue,
+            };
+            let subject = &lesson.subject;
+            let name = if subject.is_cancelled {
+                format!("{} (cancelled)", subject.subject_name)
+            } else {
+                subject.subject_name.clone()
+            };
+            let marks: Vec<String> = subject
+                .marks
+                .iter()
+                .map(|mark| mark.value.to_string())
+                .collect();
+            table.add_row(vec![
+                format!("{}–{}", lesson.begin_str, lesson.end_str),
+                name,
+                lesson.room.clone(),
+                subject.teacher.name(),
+                marks.join(" "),
+                subject.homework.clone(),
+            ]);
+        }
+        println!("{table}");
+    }
+}
+
+pub fn homework(homeworks: &[StudentHomework]) {
+    let mut table = table(["Subject", "Task", "Minutes", "Files", "Done"]);
+    for homework in homeworks {
+        let entry = &homework.homework_entry;
+        if entry.deleted_at.is_some() {
+            continue;
+        }
+        table.add_row(vec![
+            entry.subject().name.clone(),
+            entry.description.clone(),
+            entry.expected_duration.to_string(),
+            entry.attachments.len().to_string(),
+            yes_no(homework.is_ready).to_string(),
+        ]);
+    }
+    println!("{table}");
+}
+
+/// Mark with the lesson it was given on
+#[derive(Debug, Serialize)]
+pub struct LessonMark<'a> {
+    pub date: chrono::NaiveDate,
+    pub subject_name: &'a str,
+    pub mark: &'a MarkInstance,
+}
+
+pub fn marks(marks: &[LessonMark]) {
+    let mut table = table(["Date", "Subject", "Mark", "Weight", "Form"]);
+    for entry in marks {
+        let mark = entry.mark;
+        let value = if mark.is_exam {
+            format!("{} (exam)", mark.value)
+        } else {
+            mark.value.to_string()
+        };
+        table.add_row(vec![
+            entry.date.format("%d.%m.%Y").to_string(),
+            entry.subject_name.to_string(),
+            value,
+            mark.weight.to_string(),
+            mark.cause.clone(),
+        ]);
+    }
+    println!("{table}");
+}
+
+/// Average as sent by the server, `-` without marks
+pub fn average(value: Option<f32>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| format!("{:.2}", value))
+}
+
+pub fn progress(grades: &[GlobalAverageGrade]) {
+    let mut table = table(["Subject", "Average", "Hundred-based"]);
+    for grade in grades {
+        table.add_row(vec![
+            grade.subject_name.clone(),
+            average(grade.five),
+            average(grade.hundred),
+        ]);
+    }
+    println!("{table}");
+}
+
+pub fn final_marks(marks: &[FinalMark]) {
+    let mut table = table(["Subject", "Mark", "Attested", "Debt"]);
+    for mark in marks {
+        table.add_row(vec![
+            mark.subject_name.clone(),
+            mark.mark().to_string(),
+            yes_no(mark.attested).to_string(),
+            yes_no(mark.has_debt).to_string(),
+        ]);
+    }
+    println!("{table}");
+}
+
+/// Exit time of the visit, `-` while the student is inside
+pub fn exit(visit: &StudentVisit) -> String {
+    visit
+        .exit
+        .map_or_else(|| "-".to_string(), |exit| exit.format("%H:%M").to_string())
+}
+
+pub fn visits(attendance: &[StudentAttendance]) {
+    let mut table = table(["Date", "In", "Out", "Duration", "Building"]);
+    for day in attendance {
+        for visit in &day.visits {
+            table.add_row(vec![
+                day.date.format("%d.%m.%Y").to_string(),
+                visit.entrance.format("%H:%M").to_string(),
+                exit(visit),
+                visit.duration.map(format_duration).unwrap_or_default(),
+                visit.short_name.clone(),
+            ]);
+        }
+    }
+    println!("{table}");
+}
+
+pub fn plan(plan: &LessonPlan) {
+    println!("{} ({} lessons)", plan.name, plan.lesson_count);
+    let mut table = table(["Module", "Topic"]);
+    for module in &plan.modules {
+        for topic in &module.topics {
+            table.add_row(vec![module.name.clone(), topic.name.clone()]);
+        }
+    }
+    println!("{table}");
+}
+
+pub fn downloads(paths: &[PathBuf]) {
+    for path in paths {
+        println!("{}", path.display());
+    }
+    println!("Downloaded {} files", paths.len());
+}
@@ -0,0 +1,60 @@
+//! Settings of the command-line client, read from a TOML file
+
+use serde::Deserialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Contents of the config file. Command-line arguments take precedence over it
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// Auth token of the account
+    pub token: Option<String>,
+    /// ID of the child to use for representative accounts
+    pub child: Option<u64>,
+    /// Host of the diary, `https://dnevnik.mos.ru` by default
+    pub base_url: Option<String>,
+}
+
+impl Settings {
+    /// `dnevnik/config.toml` in the user's config directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("dnevnik").join("config.toml"))
+    }
+
+    /// Reads the settings from the provided file. A missing default file means empty settings,
+    /// while an explicitly provided file must exist
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Self::parse(&contents)
+                .map_err(|e| format!("invalid config file {}: {}", path.display(), e).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(format!("could not read config file {}: {}", path.display(), e).into()),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_settings() {
+        let settings = Settings::parse("token = \"abc\"\nchild = 1000001\n").unwrap();
+        assert_eq!(settings.token.as_deref(), Some("abc"));
+        assert_eq!(settings.child, Some(1000001));
+        assert!(settings.base_url.is_none());
+        assert!(Settings::parse("tokn = \"abc\"").is_err());
+    }
+}
@@ -0,0 +1,418 @@
+//! Full-screen terminal viewer of the diary
+//!
+//! All tabs share the selected day: the schedule and homework tabs show its week,
+//! the attendance tab shows its month. Data is loaded when a tab needs it and kept
+//! until the selected period changes or `r` is pressed.
+
+mod view;
+
+use crate::{at_noon, attachment_path};
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDate};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use dnevnik::model::lessons::Schedule;
+use dnevnik::prelude::*;
+use ratatui::backend::CrosstermBackend;
+use ratatui::widgets::{ListState, TableState};
+use ratatui::Terminal;
+use std::io::{self, Stdout};
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Schedule,
+    Marks,
+    Homework,
+    Attendance,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 4] = [Tab::Schedule, Tab::Marks, Tab::Homework, Tab::Attendance];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Tab::Schedule => "Schedule",
+            Tab::Marks => "Marks",
+            Tab::Homework => "Homework",
+            Tab::Attendance => "Attendance",
+        }
+    }
+
+    fn index(&self) -> usize {
+        Self::ALL.iter().position(|tab| tab == self).unwrap()
+    }
+
+    fn next(&self) -> Tab {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn previous(&self) -> Tab {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// Data of a period along with the period it was loaded for
+#[derive(Debug)]
+struct Loaded<K, T> {
+    key: K,
+    data: T,
+}
+
+/// State of the viewer
+pub struct App {
+    diary: Diary,
+    download_dir: PathBuf,
+    pub tab: Tab,
+    /// Selected day
+    pub day: NaiveDate,
+    schedules: Option<Loaded<NaiveDate, Vec<Schedule>>>,
+    progress: Option<Vec<GlobalAverageGrade>>,
+    homeworks: Option<Loaded<NaiveDate, Vec<StudentHomework>>>,
+    visits: Option<Loaded<(i32, u32), Vec<StudentAttendance>>>,
+    pub lessons: TableState,
+    pub subjects: ListState,
+    pub homework: TableState,
+    /// Message shown in the status line
+    pub status: String,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(diary: Diary, download_dir: PathBuf) -> Self {
+        Self {
+            diary,
+            download_dir,
+            tab: Tab::Schedule,
+            day: Local::now().date_naive(),
+            schedules: None,
+            progress: None,
+            homeworks: None,
+            visits: None,
+            lessons: TableState::default(),
+            subjects: ListState::default().with_selected(Some(0)),
+            homework: TableState::default().with_selected(Some(0)),
+            status: String::new(),
+            quit: false,
+        }
+    }
+
+    pub fn student_name(&self) -> String {
+        let account = match self.diary.child() {
+            Some(child) => &child.parent_account,
+            None => &self.diary.profile.account,
+        };
+        format!("{} {}", account.last_name, account.first_name)
+    }
+
+    /// Monday of the selected week
+    pub fn week_start(&self) -> NaiveDate {
+        week_start(self.day)
+    }
+
+    fn month(&self) -> (i32, u32) {
+        (self.day.year(), self.day.month())
+    }
+
+    pub fn schedules(&self) -> &[Schedule] {
+        self.schedules
+            .as_ref()
+            .map(|loaded| loaded.data.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn progress(&self) -> &[GlobalAverageGrade] {
+        self.progress.as_deref().unwrap_or_default()
+    }
+
+    pub fn homeworks(&self) -> &[StudentHomework] {
+        self.homeworks
+            .as_ref()
+            .map(|loaded| loaded.data.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn visits(&self) -> &[StudentAttendance] {
+        self.visits
+            .as_ref()
+            .map(|loaded| loaded.data.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Whether the current tab lacks the data of the selected period
+    fn needs_load(&self) -> bool {
+        match self.tab {
+            Tab::Schedule => !matches!(&self.schedules, Some(l) if l.key == self.week_start()),
+            Tab::Marks => self.progress.is_none(),
+            Tab::Homework => !matches!(&self.homeworks, Some(l) if l.key == self.week_start()),
+            Tab::Attendance => !matches!(&self.visits, Some(l) if l.key == self.month()),
+        }
+    }
+
+    async fn load(&mut self) -> dnevnik::error::Result<()> {
+        let monday = week_start(self.day);
+        let sunday = monday + ChronoDuration::days(6);
+        match self.tab {
+            Tab::Schedule => {
+                let mut data = vec![];
+                for (_, schedule) in self
+                    .diary
+                    .schedule_range(at_noon(monday), at_noon(sunday), RangeOptions::default())
+                    .await
+                {
+                    data.push(schedule?);
+                }
+                self.schedules = Some(Loaded { key: monday, data });
+                self.lessons.select(None);
+            }
+            Tab::Marks => {
+                self.progress = Some(self.diary.progress().await?);
+            }
+            Tab::Homework => {
+                let data = self
+                    .diary
+                    .homework(at_noon(monday), at_noon(sunday))
+                    .await?;
+                self.homeworks = Some(Loaded { key: monday, data });
+                self.homework.select(Some(0));
+            }
+            Tab::Attendance => {
+                let first = self.day.with_day(1).unwrap();
+                let last = last_day_of_month(first);
+                let data = self.diary.visits(at_noon(first), at_noon(last)).await?;
+                self.visits = Some(Loaded {
+                    key: self.month(),
+                    data,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets the data of the current tab, so it is loaded again
+    fn reload(&mut self) {
+        match self.tab {
+            Tab::Schedule => self.schedules = None,
+            Tab::Marks => self.progress = None,
+            Tab::Homework => self.homeworks = None,
+            Tab::Attendance => self.visits = None,
+        }
+    }
+
+    /// Records the selected period of the current tab as loaded with no data
+    fn mark_loaded(&mut self) {
+        let monday = self.week_start();
+        match self.tab {
+            Tab::Schedule => {
+                self.schedules = Some(Loaded {
+                    key: monday,
+                    data: vec![],
+                })
+            }
+            Tab::Marks => self.progress = Some(vec![]),
+            Tab::Homework => {
+                self.homeworks = Some(Loaded {
+                    key: monday,
+                    data: vec![],
+                })
+            }
+            Tab::Attendance => {
+                self.visits = Some(Loaded {
+                    key: self.month(),
+                    data: vec![],
+                })
+            }
+        }
+    }
+
+    fn move_day(&mut self, days: i64) {
+        self.day += ChronoDuration::days(days);
+        self.lessons.select(None);
+    }
+
+    fn select(state: &mut Option<usize>, len: usize, delta: isize) {
+        if len == 0 {
+            *state = None;
+            return;
+        }
+        let current = state.map(|index| index as isize).unwrap_or(-1);
+        *state = Some((current + delta).clamp(0, len as isize - 1) as usize);
+    }
+
+    fn lesson_count(&self) -> usize {
+        self.schedules()
+            .iter()
+            .find(|schedule| schedule.date == self.day)
+            .map(|schedule| schedule.lessons.len())
+            .unwrap_or(0)
+    }
+
+    async fn download_selected(&mut self) {
+        let homework = match self
+            .homework
+            .selected()
+            .and_then(|index| self.homeworks().get(index))
+        {
+            Some(homework) => homework.clone(),
+            None => return,
+        };
+        let attachments = &homework.homework_entry.attachments;
+        if attachments.is_empty() {
+            self.status = "The homework has no attachments".to_string();
+            return;
+        }
+        if let Err(e) = tokio::fs::create_dir_all(&self.download_dir).await {
+            self.status = format!("Could not create {}: {}", self.download_dir.display(), e);
+            return;
+        }
+        for attachment in attachments {
+            let path = attachment_path(&self.download_dir, attachment);
+            if let Err(e) = self.diary.download_attachment(path, attachment).await {
+                self.status = format!("Could not download {}: {}", attachment.file_name, e);
+                return;
+            }
+        }
+        self.status = format!(
+            "Downloaded {} files to {}",
+            attachments.len(),
+            self.download_dir.display()
+        );
+    }
+
+    async fn handle_key(&mut self, key: KeyEvent) {
+        self.status.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Tab => self.tab = self.tab.next(),
+            KeyCode::BackTab => self.tab = self.tab.previous(),
+            KeyCode::Char(c @ '1'..='4') => self.tab = Tab::ALL[c as usize - '1' as usize],
+            KeyCode::Char('r') => self.reload(),
+            KeyCode::Char('t') => self.day = Local::now().date_naive(),
+            KeyCode::Left => self.move_day(-1),
+            KeyCode::Right => self.move_day(1),
+            KeyCode::PageUp => self.move_day(-7),
+            KeyCode::PageDown => self.move_day(7),
+            KeyCode::Up | KeyCode::Down => {
+                let delta = if key.code == KeyCode::Up { -1 } else { 1 };
+                match self.tab {
+                    Tab::Schedule => {
+                        let len = self.lesson_count();
+                        Self::select(self.lessons.selected_mut(), len, delta)
+                    }
+                    Tab::Marks => {
+                        let len = self.progress().len();
+                        let mut selected = self.subjects.selected();
+                        Self::select(&mut selected, len, delta);
+                        self.subjects.select(selected);
+                    }
+                    Tab::Homework => {
+                        let len = self.homeworks().len();
+                        Self::select(self.homework.selected_mut(), len, delta)
+                    }
+                    Tab::Attendance => self.move_day(7 * delta as i64),
+                }
+            }
+            KeyCode::Char('d') if self.tab == Tab::Homework => self.download_selected().await,
+            _ => {}
+        }
+    }
+}
+
+/// Monday of the week containing the day
+pub fn week_start(day: NaiveDate) -> NaiveDate {
+    day - ChronoDuration::days(day.weekday().num_days_from_monday().into())
+}
+
+fn last_day_of_month(first: NaiveDate) -> NaiveDate {
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+    };
+    next.unwrap().pred_opt().unwrap()
+}
+
+/// Weeks of the month containing the day, Monday first. Days of other months are `None`
+pub fn month_grid(day: NaiveDate) -> Vec<[Option<NaiveDate>; 7]> {
+    let first = day.with_day(1).unwrap();
+    let last = last_day_of_month(first);
+    let mut weeks = vec![];
+    let mut monday = week_start(first);
+    while monday <= last {
+        let mut week = [None; 7];
+        for (offset, cell) in week.iter_mut().enumerate() {
+            let date = monday + ChronoDuration::days(offset as i64);
+            if date.month() == first.month() {
+                *cell = Some(date);
+            }
+        }
+        weeks.push(week);
+        monday += ChronoDuration::days(7);
+    }
+    weeks
+}
+
+/// Restores the terminal even if the viewer panics
+struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+        let _ = io::stdout().execute(LeaveAlternateScreen);
+    }
+}
+
+/// Runs the viewer until the user quits
+pub async fn run(diary: Diary, download_dir: PathBuf) -> io::Result<()> {
+    terminal::enable_raw_mode()?;
+    let _guard = TerminalGuard;
+    io::stdout().execute(EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut app = App::new(diary, download_dir);
+    while !app.quit {
+        if app.needs_load() {
+            draw_loading(&mut terminal, &mut app)?;
+            if let Err(e) = app.load().await {
+                app.status = format!("Could not load the {}: {}", app.tab.title(), e);
+                // do not retry on every frame, `r` loads the data again
+                app.mark_loaded();
+            }
+        }
+        terminal.draw(|frame| view::draw(frame, &mut app))?;
+        if event::poll(Duration::from_millis(250))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    app.handle_key(key).await;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn draw_loading(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+) -> io::Result<()> {
+    app.status = "Loading…".to_string();
+    terminal.draw(|frame| view::draw(frame, app))?;
+    app.status.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_month_grid() {
+        let grid = month_grid(NaiveDate::from_ymd_opt(2022, 10, 12).unwrap());
+        // October 2022 starts on Saturday and ends on Monday
+        assert_eq!(grid.len(), 6);
+        assert_eq!(grid[0][..5], [None; 5]);
+        assert_eq!(grid[0][5], NaiveDate::from_ymd_opt(2022, 10, 1));
+        assert_eq!(grid[5][0], NaiveDate::from_ymd_opt(2022, 10, 31));
+        assert_eq!(grid[5][1], None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Drawing of the viewer tabs
+
+use super::{month_grid, App, Tab};
+use crate::output::{average, exit};
+use chrono::{Datelike, Duration, Local};
+use dnevnik::model::attendance::format_duration;
+use dnevnik::model::lessons::ScheduleActivity;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, List, ListItem, Paragraph, Row, Table, Tabs, Wrap};
+use ratatui::Frame;
+
+const HELP: &str = "Tab/1-4 switch  ←/→ day  PgUp/PgDn week  ↑/↓ select  t today  r reload  q quit";
+
+fn selected() -> Style {
+    Style::default().add_modifier(Modifier::REVERSED)
+}
+
+pub fn draw(frame: &mut Frame, app: &mut App) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.size());
+
+    let titles = Tab::ALL
+        .iter()
+        .enumerate()
+        .map(|(index, tab)| format!("{} {}", index + 1, tab.title()));
+    let tabs = Tabs::new(titles)
+        .select(Tab::ALL.iter().position(|tab| *tab == app.tab).unwrap())
+        .highlight_style(Style::default().bold().fg(Color::Yellow))
+        .block(Block::bordered().title(format!(
+            " {} — {} ",
+            app.student_name(),
+            app.day.format("%a %d.%m.%Y")
+        )));
+    frame.render_widget(tabs, header);
+
+    match app.tab {
+        Tab::Schedule => draw_schedule(frame, app, body),
+        Tab::Marks => draw_marks(frame, app, body),
+        Tab::Homework => draw_homework(frame, app, body),
+        Tab::Attendance => draw_attendance(frame, app, body),
+    }
+
+    let status = if app.status.is_empty() {
+        let mut help = HELP.to_string();
+        if app.tab == Tab::Homework {
+            help.push_str("  d download");
+        }
+        Line::from(help).dim()
+    } else {
+        Line::from(app.status.as_str()).yellow()
+    };
+    frame.render_widget(Paragraph::new(status), footer);
+}
+
+fn draw_schedule(frame: &mut Frame, app: &mut App, area: Rect) {
+    let [days_area, lessons_area] =
+        Layout::horizontal([Constraint::Length(24), Constraint::Min(0)]).areas(area);
+    let monday = app.week_start();
+    let days: Vec<ListItem> = (0..7)
+        .map(|offset| {
+            let date = monday + Duration::days(offset);
+            let lessons = app
+                .schedules()
+                .iter()
+                .find(|schedule| schedule.date == date)
+                .map(|schedule| {
+                    schedule
+                        .lessons
+                        .iter()
+                        .filter(|activity| matches!(activity, ScheduleActivity::Lesson(_)))
+                        .count()
+                })
+                .unwrap_or(0);
+            let item = ListItem::new(format!("{}  {} lessons", date.format("%a %d.%m"), lessons));
+            if date == app.day {
+                item.style(selected())
+            } else {
+                item
+            }
+        })
+        .collect();
+    frame.render_widget(
+        List::new(days).block(Block::bordered().title(" Week ")),
+        days_area,
+    );
+
+    let schedule = app
+        .schedules()
+        .iter()
+        .find(|schedule| schedule.date == app.day);
+    let rows: Vec<Row> = schedule
+        .map(|schedule| schedule.lessons.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|activity| match activity {
+            ScheduleActivity::Lesson(lesson) => {
+                let subject = &lesson.subject;
+                let marks: Vec<String> = subject
+                    .marks
+                    .iter()
+                    .map(|mark| mark.value.to_string())
+                    .collect();
+                let row = Row::new(vec![
+                    Cell::from(format!("{}–{}", lesson.begin_str, lesson.end_str)),
+                    Cell::from(subject.subject_name.clone()),
+                    Cell::from(lesson.room.clone()),
+                    Cell::from(marks.join(" ")).green().bold(),
+                    Cell::from(subject.homework.clone()),
+                ]);
+                if subject.is_cancelled {
+                    row.crossed_out().dim()
+                } else if subject.is_missed_lesson {
+                    row.red()
+                } else {
+                    row
+                }
+            }
+            ScheduleActivity::Break(pause) => Row::new(vec![
+                Cell::from(""),
+                Cell::from(format!("{} {} min", pause.info, pause.duration / 60)),
+            ])
+            .dim(),
+        })
+        .collect();
+    let title = match schedule {
+        Some(schedule) => format!(" {} ", schedule.summary),
+        None => " No lessons ".to_string(),
+    };
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(11),
+            Constraint::Percentage(30),
+            Constraint::Length(6),
+            Constraint::Length(8),
+            Constraint::Percentage(50),
+        ],
+    )
+    .header(Row::new(["Time", "Subject", "Room", "Marks", "Homework"]).bold())
+    .highlight_style(selected())
+    .block(Block::bordered().title(title));
+    frame.render_stateful_widget(table, lessons_area, &mut app.lessons);
+}
+
+fn draw_marks(frame: &mut Frame, app: &mut App, area: Rect) {
+    let [subjects_area, periods_area] =
+        Layout::horizontal([Constraint::Percentage(35), Constraint::Min(0)]).areas(area);
+    let subjects: Vec<ListItem> = app
+        .progress()
+        .iter()
+        .map(|grade| {
+            ListItem::new(format!(
+                "{:>5}  {}",
+                average(grade.five),
+                grade.subject_name
+            ))
+        })
+        .collect();
+    let list = List::new(subjects)
+        .highlight_style(selected())
+        .block(Block::bordered().title(" Subjects "));
+    frame.render_stateful_widget(list, subjects_area, &mut app.subjects);
+
+    let grade = app
+        .subjects
+        .selected()
+        .and_then(|index| app.progress().get(index));
+    let rows: Vec<Row> = grade
+        .map(|grade| grade.periods.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|period| {
+            let marks: Vec<String> = period
+                .marks
+                .iter()
+                .map(|mark| {
+                    if mark.weight > 1.0 {
+                        format!("{}×{}", mark.value, mark.weight)
+                    } else {
+                        mark.value.to_string()
+                    }
+                })
+                .collect();
+            Row::new(vec![
+                period.name.clone(),
+                average(period.five),
+                marks.join(" "),
+            ])
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(16),
+            Constraint::Length(8),
+            Constraint::Min(0),
+        ],
+    )
+    .header(Row::new(["Period", "Average", "Marks"]).bold())
+    .block(Block::bordered().title(format!(
+        " {} ",
+        grade.map(|grade| grade.subject_name.as_str()).unwrap_or("Periods")
+    )));
+    frame.render_widget(table, periods_area);
+}
+
+fn draw_homework(frame: &mut Frame, app: &mut App, area: Rect) {
+    let [list_area, details_area] =
+        Layout::vertical([Constraint::Percentage(60), Constraint::Min(0)]).areas(area);
+    let monday = app.week_start();
+    let rows: Vec<Row> = app
+        .homeworks()
+        .iter()
+        .map(|homework| {
+            let entry = &homework.homework_entry;
+            let row = Row::new(vec![
+                entry.subject().name.clone(),
+                entry.description.clone(),
+                format!("{} min", entry.expected_duration),
+                entry.attachments.len().to_string(),
+            ]);
+            if homework.is_ready || entry.deleted_at.is_some() {
+                row.dim()
+            } else {
+                row
+            }
+        })
+        .collect();
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(25),
+            Constraint::Percentage(55),
+            Constraint::Length(8),
+            Constraint::Length(5),
+        ],
+    )
+    .header(Row::new(["Subject", "Task", "Time", "Files"]).bold())
+    .highlight_style(selected())
+    .block(Block::bordered().title(format!(
+        " {} – {} ",
+        monday.format("%d.%m"),
+        (monday + Duration::days(6)).format("%d.%m")
+    )));
+    frame.render_stateful_widget(table, list_area, &mut app.homework);
+
+    let mut lines = vec![];
+    if let Some(homework) = app
+        .homework
+        .selected()
+        .and_then(|index| app.homeworks().get(index))
+    {
+        let entry = &homework.homework_entry;
+        lines.push(Line::from(entry.description.clone()));
+        lines.push(Line::default());
+        for attachment in &entry.attachments {
+            lines.push(Line::from(vec![
+                Span::from("📎 "),
+                Span::from(attachment.file_name.clone()).underlined(),
+                Span::from(format!(" ({} bytes)", attachment.file_size)).dim(),
+            ]));
+        }
+    }
+    let details = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::bordered().title(" Details "));
+    frame.render_widget(details, details_area);
+}
+
+fn draw_attendance(frame: &mut Frame, app: &mut App, area: Rect) {
+    let [calendar_area, visits_area] =
+        Layout::horizontal([Constraint::Length(38), Constraint::Min(0)]).areas(area);
+    let today = Local::now().date_naive();
+    let rows: Vec<Row> = month_grid(app.day)
+        .into_iter()
+        .map(|week| {
+            Row::new(week.map(|date| {
+                let date = match date {
+                    Some(date) => date,
+                    None => return Cell::from(""),
+                };
+                let visited = app
+                    .visits()
+                    .iter()
+                    .any(|day| day.date == date && !day.visits.is_empty());
+                let mut style = Style::default();
+                if visited {
+                    style = style.fg(Color::Green).bold();
+                } else if date < today && date.weekday().number_from_monday() <= 5 {
+                    style = style.fg(Color::Red);
+                }
+                if date == app.day {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Cell::from(format!("{:>3}", date.day())).style(style)
+            }))
+        })
+        .collect();
+    let calendar = Table::new(rows, [Constraint::Length(4); 7])
+        .header(Row::new(["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]).bold())
+        .block(Block::bordered().title(format!(" {} ", app.day.format("%B %Y"))));
+    frame.render_widget(calendar, calendar_area);
+
+    let lines: Vec<Line> = app
+        .visits()
+        .iter()
+        .filter(|day| day.date == app.day)
+        .flat_map(|day| &day.visits)
+        .map(|visit| {
+            Line::from(format!(
+                "{} – {}  ({})  {}",
+                visit.entrance.format("%H:%M"),
+                exit(visit),
+                visit.duration.map(format_duration).unwrap_or_default(),
+                visit.short_name
+            ))
+        })
+        .collect();
+    let visits = Paragraph::new(lines).block(Block::bordered().title(" Visits "));
+    frame.render_widget(visits, visits_area);
+}
@@ -0,0 +1,124 @@
+//! Synchronous wrapper around the [Diary](crate::diary::Diary), for callers without an async runtime
+//!
+//! Every blocking diary owns a single-threaded tokio runtime that drives its requests.
+//! Methods must not be called from within an async context.
+
+use crate::auth::AuthToken;
+use crate::diary::DiaryBuilder;
+use crate::error::Result;
+use crate::model::attendance::StudentAttendance;
+use crate::model::hw::{HomeworkAttachment, StudentHomework};
+use crate::model::lessons::{AcademicYear, FinalMark, LessonInstance, LessonPlan, Schedule};
+use crate::model::marks::GlobalAverageGrade;
+use crate::model::{StudentDetails, StudentProfile, StudentSession};
+use chrono::{DateTime, Utc};
+use std::future::Future;
+use std::path::PathBuf;
+use tokio::runtime::Runtime;
+
+/// Blocking counterpart of the [Diary](crate::diary::Diary)
+#[derive(Debug)]
+pub struct Diary {
+    inner: crate::diary::Diary,
+    runtime: Runtime,
+}
+
+impl Diary {
+    pub fn new<S: Into<String>>(token: S) -> Result<Self> {
+        Self::with_builder(DiaryBuilder::new(token))
+    }
+
+    /// Builds the diary from a builder with custom configuration
+    pub fn with_builder(builder: DiaryBuilder) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let inner = runtime.block_on(builder.build())?;
+        Ok(Self { inner, runtime })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Profile of the account
+    pub fn profile(&self) -> &StudentProfile {
+        &self.inner.profile
+    }
+
+    /// Details of the student whose data is requested by this diary
+    pub fn child(&self) -> Option<&StudentDetails> {
+        self.inner.child()
+    }
+
+    /// Selects the child whose data is requested by this diary
+    pub fn select_child(&mut self, child_id: u64) -> Result<()> {
+        self.inner.select_child(child_id)
+    }
+
+    /// Token that will be used for the next request, along with its expiry
+    pub fn token(&self) -> Result<AuthToken> {
+        self.block_on(self.inner.token())
+    }
+
+    pub fn session(&self) -> Result<StudentSession> {
+        self.block_on(self.inner.session())
+    }
+
+    pub fn academic_years(&self) -> Result<Vec<AcademicYear>> {
+        self.block_on(self.inner.academic_years())
+    }
+
+    pub fn schedule(&self, date: DateTime<Utc>) -> Result<Schedule> {
+        self.block_on(self.inner.schedule(date))
+    }
+
+    pub fn final_marks(&self, year: &AcademicYear) -> Result<Vec<FinalMark>> {
+        self.block_on(self.inner.final_marks(year))
+    }
+
+    /// Gets module lesson plan for the provided lesson
+    pub fn lesson_plan(&self, lesson: &LessonInstance) -> Result<LessonPlan> {
+        self.block_on(self.inner.lesson_plan(lesson))
+    }
+
+    pub fn homework(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<StudentHomework>> {
+        self.block_on(self.inner.homework(from, to))
+    }
+
+    pub fn download_attachment(
+        &self,
+        path: PathBuf,
+        attachment: &HomeworkAttachment,
+    ) -> Result<()> {
+        self.block_on(self.inner.download_attachment(path, attachment))
+    }
+
+    /// Gets the progress report for the current student
+    pub fn progress(&self) -> Result<Vec<GlobalAverageGrade>> {
+        self.block_on(self.inner.progress())
+    }
+
+    pub fn visits(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<StudentAttendance>> {
+        self.block_on(self.inner.visits(from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cassette::ReplayTransport;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_blocking_diary() -> anyhow::Result<()> {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let diary = Diary::with_builder(
+            DiaryBuilder::new("replay").transport(ReplayTransport::new(fixtures)),
+        )?;
+        let date = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        assert!(!diary.schedule(date)?.lessons.is_empty());
+        assert!(!diary.progress()?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+//! Opt-in cache of API responses with per-endpoint TTLs
+//!
+//! Only successful `GET` requests to endpoints with a configured TTL are cached, keyed by
+//! their full URL and the account, so a store can be shared by clients of several accounts.
+//! Expired entries carrying an `ETag` or `Last-Modified` validator are revalidated with
+//! a conditional request instead of being downloaded again.
+
+use crate::config::{DiaryConfig, Endpoint};
+use crate::error::{DnevnikError, Result};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// File names of the disk cache are truncated to this length
+const MAX_FILE_NAME: usize = 200;
+
+/// Cached response body along with its validators
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub body: Bytes,
+    /// Value of the `ETag` header of the response
+    pub etag: Option<String>,
+    /// Value of the `Last-Modified` header of the response
+    pub last_modified: Option<String>,
+    /// Time at which the response was received or last revalidated
+    pub stored_at: DateTime<Utc>,
+}
+
+impl CacheEntry {
+    /// Whether this entry is older than the provided TTL
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        let age = Utc::now() - self.stored_at;
+        age.to_std().map(|age| age >= ttl).unwrap_or(false)
+    }
+
+    /// Whether this entry can be revalidated with a conditional request
+    pub fn has_validators(&self) -> bool {
+        self.etag.is_some() || self.last_modified.is_some()
+    }
+}
+
+/// Storage of cached responses
+#[async_trait]
+pub trait CacheStore: Debug + Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<CacheEntry>>;
+
+    async fn put(&self, key: &str, entry: CacheEntry) -> Result<()>;
+
+    /// Removes all entries with keys starting with the prefix
+    async fn remove(&self, prefix: &str) -> Result<()>;
+}
+
+/// Cache store keeping the entries in memory
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    async fn put(&self, key: &str, entry: CacheEntry) -> Result<()> {
+        self.entries.lock().unwrap().insert(key.to_string(), entry);
+        Ok(())
+    }
+
+    async fn remove(&self, prefix: &str) -> Result<()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(prefix));
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    body_base64: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    stored_at: DateTime<Utc>,
+}
+
+/// Cache store keeping one JSON file per entry in a directory
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let name: String = key
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .take(MAX_FILE_NAME)
+            .collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    async fn read(&self, path: PathBuf) -> Result<Option<DiskEntry>> {
+        let contents = match tokio::fs::read(&path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|e| DnevnikError::decode(&path.to_string_lossy(), &contents, e))
+    }
+}
+
+#[async_trait]
+impl CacheStore for DiskCache {
+    async fn get(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let entry = match self.read(self.path(key)).await? {
+            // different keys may map to the same file name
+            Some(entry) if entry.key == key => entry,
+            _ => return Ok(None),
+        };
+        let body = STANDARD
+            .decode(entry.body_base64)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Ok(Some(CacheEntry {
+            body: Bytes::from(body),
+            etag: entry.etag,
+            last_modified: entry.last_modified,
+            stored_at: entry.stored_at,
+        }))
+    }
+
+    async fn put(&self, key: &str, entry: CacheEntry) -> Result<()> {
+        let entry = DiskEntry {
+            key: key.to_string(),
+            body_base64: STANDARD.encode(&entry.body),
+            etag: entry.etag,
+            last_modified: entry.last_modified,
+            stored_at: entry.stored_at,
+        };
+        let contents = serde_json::to_vec(&entry).map_err(|e| DnevnikError::encode(key, e))?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.path(key), contents).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, prefix: &str) -> Result<()> {
+        let mut dir = match tokio::fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(file) = dir.next_entry().await? {
+            let matches = match self.read(file.path()).await {
+                Ok(Some(entry)) => entry.key.starts_with(prefix),
+                // unreadable files are not ours to delete
+                _ => false,
+            };
+            if matches {
+                tokio::fs::remove_file(file.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cache configuration of a diary: the store and TTLs of the cached endpoints
+#[derive(Debug, Clone)]
+pub struct Cache {
+    store: Arc<dyn CacheStore>,
+    /// TTLs of the cached endpoints. Endpoints without a TTL are never cached
+    pub ttls: HashMap<Endpoint, Duration>,
+    /// TTL of the schedules of past days, which should not change anymore.
+    /// Overrides the TTL of [Endpoint::Schedule] for such days
+    pub past_schedule_ttl: Option<Duration>,
+}
+
+impl Cache {
+    /// Creates a cache with default TTLs for rarely changing endpoints: a day for academic years,
+    /// lesson plans and past schedules, an hour for the profile
+    pub fn new<S: CacheStore + 'static>(store: S) -> Self {
+        const HOUR: Duration = Duration::from_secs(60 * 60);
+        Self {
+            store: Arc::new(store),
+            ttls: HashMap::from([
+                (Endpoint::Profile, HOUR),
+                (Endpoint::AcademicYears, 24 * HOUR),
+                (Endpoint::LessonPlans, 24 * HOUR),
+            ]),
+            past_schedule_ttl: Some(24 * HOUR),
+        }
+    }
+
+    /// Cache kept in memory
+    pub fn memory() -> Self {
+        Self::new(MemoryCache::new())
+    }
+
+    /// Cache stored in the provided directory
+    pub fn disk<P: Into<PathBuf>>(dir: P) -> Self {
+        Self::new(DiskCache::new(dir))
+    }
+
+    /// Sets the TTL of the endpoint, enabling caching of its responses
+    pub fn ttl(mut self, endpoint: Endpoint, ttl: Duration) -> Self {
+        self.ttls.insert(endpoint, ttl);
+        self
+    }
+
+    /// Disables caching of the endpoint
+    pub fn no_cache(mut self, endpoint: Endpoint) -> Self {
+        self.ttls.remove(&endpoint);
+        self
+    }
+
+    /// Sets the TTL of schedules of past days
+    pub fn past_schedule_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.past_schedule_ttl = ttl;
+        self
+    }
+
+    pub fn store(&self) -> &dyn CacheStore {
+        self.store.as_ref()
+    }
+
+    /// TTL of the request to the provided URL, `None` if it should not be cached
+    pub(crate) fn ttl_for(&self, config: &DiaryConfig, url: &Url) -> Option<Duration> {
+        if let Some(ttl) = self.past_schedule_ttl {
+            if is_endpoint(config, Endpoint::Schedule, url) && is_past_day(url) {
+                return Some(ttl);
+            }
+        }
+        self.ttls
+            .iter()
+            .find(|(endpoint, _)| is_endpoint(config, **endpoint, url))
+            .map(|(_, ttl)| *ttl)
+    }
+}
+
+/// Whether the URL points to the endpoint, possibly with a query or a trailing ID
+fn is_endpoint(config: &DiaryConfig, endpoint: Endpoint, url: &Url) -> bool {
+    url.as_str()
+        .strip_prefix(&config.endpoint_url(endpoint))
+        .map(|rest| rest.is_empty() || rest.starts_with(['?', '/']))
+        .unwrap_or(false)
+}
+
+/// Whether the `date` query parameter of the URL is before today
+fn is_past_day(url: &Url) -> bool {
+    url.query_pairs()
+        .find(|(key, _)| key == "date")
+        .and_then(|(_, date)| date.parse::<NaiveDate>().ok())
+        .map(|date| date < Utc::now().date_naive())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diary::Diary;
+    use crate::testing::{diary_with, fixture, profile_transport, TempDir};
+    use crate::transport::{HttpResponse, MemoryTransport};
+    use reqwest::header::{HeaderValue, ETAG};
+    use reqwest::{Method, StatusCode};
+
+    fn years_requests(transport: &MemoryTransport) -> usize {
+        transport
+            .requests()
+            .iter()
+            .filter(|request| request.url.path() == "/core/api/academic_years")
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_cache_and_invalidate() -> anyhow::Result<()> {
+        let transport = Arc::new(profile_transport().json(
+            "/core/api/academic_years",
+            &fixture("get_core_api_academic_years.json"),
+        ));
+        let diary = diary_with(transport.clone())
+            .cache(Cache::memory())
+            .build()
+            .await?;
+        diary.academic_years().await?;
+        diary.academic_years().await?;
+        assert_eq!(years_requests(&transport), 1);
+        diary.invalidate(Endpoint::AcademicYears).await?;
+        assert_eq!(diary.academic_years().await?.len(), 3);
+        assert_eq!(years_requests(&transport), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_cache_per_account() -> anyhow::Result<()> {
+        let transport = Arc::new(profile_transport());
+        let cache = Cache::memory();
+        for token in ["first", "second", "first"] {
+            Diary::builder(token)
+                .transport(transport.clone())
+                .cache(cache.clone())
+                .build()
+                .await?;
+        }
+        // the profile of another token is never served from the cache
+        assert_eq!(transport.requests().len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revalidation() -> anyhow::Result<()> {
+        let mut tagged = HttpResponse::json(&fixture("get_core_api_academic_years.json"))?;
+        tagged
+            .headers
+            .insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let transport = Arc::new(
+            profile_transport()
+                .route(Method::GET, "/core/api/academic_years", tagged)
+                .route(
+                    Method::GET,
+                    "/core/api/academic_years",
+                    HttpResponse::new(StatusCode::NOT_MODIFIED, ""),
+                ),
+        );
+        let dir = TempDir::new("cache");
+        let diary = diary_with(transport.clone())
+            .cache(Cache::disk(dir.path()).ttl(Endpoint::AcademicYears, Duration::ZERO))
+            .build()
+            .await?;
+        diary.academic_years().await?;
+        // the expired entry is revalidated and served from the cache
+        assert_eq!(diary.academic_years().await?.len(), 3);
+        let last = transport.requests().pop().unwrap();
+        assert_eq!(last.headers["If-None-Match"], "\"v1\"");
+        let key = format!("{}#{}", last.url, diary.profile.account.id);
+        assert!(DiskCache::new(dir.path()).get(&key).await?.is_some());
+        diary.clear_cache().await?;
+        assert!(DiskCache::new(dir.path()).get(&key).await?.is_none());
+        Ok(())
+    }
+}
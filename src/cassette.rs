@@ -0,0 +1,289 @@
+//! Recording real API responses to fixture files and replaying them later
+//!
+//! Fixtures are stored one file per request, named after the method, path and sorted
+//! query of the request. Personal data is scrubbed from the JSON bodies before writing.
+
+use crate::error::{DnevnikError, Result};
+use crate::transport::{HttpRequest, HttpResponse, ReqwestTransport, Transport};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Environment variable switching [from_env] to the record mode
+pub const RECORD_ENV: &str = "DNEVNIK_RECORD";
+
+/// Single recorded response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// Body of a JSON response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+    /// Base64 encoded body of a non-JSON response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+
+impl Fixture {
+    fn into_response(self) -> Result<HttpResponse> {
+        let body = match (self.body, self.body_base64) {
+            (Some(json), _) => Bytes::from(json.to_string()),
+            (None, Some(encoded)) => STANDARD
+                .decode(encoded)
+                .map(Bytes::from)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            (None, None) => Bytes::new(),
+        };
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = self.content_type {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
+        }
+        Ok(HttpResponse {
+            status: StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK),
+            headers,
+            body,
+        })
+    }
+}
+
+/// Name of the fixture file for the provided request
+pub fn fixture_name(request: &HttpRequest) -> String {
+    let mut name = format!(
+        "{}{}",
+        request.method.as_str().to_lowercase(),
+        request.url.path().replace('/', "_")
+    );
+    let query = sorted_query(request);
+    if !query.is_empty() {
+        name.push('@');
+        name.push_str(&query);
+    }
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' | '=' | '&' | '@' => c,
+            _ => '_',
+        })
+        .collect();
+    format!("{}.json", name)
+}
+
+fn sorted_query(request: &HttpRequest) -> String {
+    let mut pairs: Vec<(String, String)> = request.url.query_pairs().into_owned().collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Replaces values of personal fields in JSON bodies with placeholders
+#[derive(Debug, Clone)]
+pub struct Scrubber {
+    /// Field names and the values they are replaced with
+    pub replacements: Vec<(String, Value)>,
+}
+
+impl Default for Scrubber {
+    fn default() -> Self {
+        let text = |s: &str| Value::String(s.to_string());
+        let nil_uuid = text("00000000-0000-0000-0000-000000000000");
+        Self {
+            replacements: vec![
+                ("last_name".to_string(), text("Фамилия")),
+                ("first_name".to_string(), text("Имя")),
+                ("middle_name".to_string(), text("Отчество")),
+                ("principal".to_string(), text("Фамилия Имя Отчество")),
+                ("snils".to_string(), text("000-000-000 00")),
+                ("phone".to_string(), text("0000000000")),
+                ("phone_number".to_string(), text("0000000000")),
+                ("email".to_string(), text("user@example.com")),
+                ("birth_date".to_string(), text("2007-01-01")),
+                ("date_of_birth".to_string(), text("2007-01-01")),
+                ("contingent_guid".to_string(), nil_uuid.clone()),
+                ("person_id".to_string(), nil_uuid),
+                ("authentication_token".to_string(), text("token")),
+            ],
+        }
+    }
+}
+
+impl Scrubber {
+    /// Scrubs the value recursively, keeping `null` values as they are
+    pub fn scrub(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, field) in map.iter_mut() {
+                    match self.replacements.iter().find(|(name, _)| name == key) {
+                        Some((_, replacement)) if !field.is_null() => *field = replacement.clone(),
+                        _ => self.scrub(field),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.scrub(value)),
+            _ => {}
+        }
+    }
+}
+
+/// Transport that forwards requests to another transport and writes scrubbed responses to fixtures
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    dir: PathBuf,
+    scrubber: Scrubber,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    pub fn new<P: Into<PathBuf>>(inner: T, dir: P) -> Self {
+        Self {
+            inner,
+            dir: dir.into(),
+            scrubber: Scrubber::default(),
+        }
+    }
+
+    pub fn scrubber(mut self, scrubber: Scrubber) -> Self {
+        self.scrubber = scrubber;
+        self
+    }
+}
+
+#[async_trait]
+impl<T: Transport> Transport for RecordingTransport<T> {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let name = fixture_name(&request);
+        let mut fixture = Fixture {
+            method: request.method.to_string(),
+            path: request.url.path().to_string(),
+            query: sorted_query(&request),
+            status: 0,
+            content_type: None,
+            body: None,
+            body_base64: None,
+        };
+        let response = self.inner.send(request).await?;
+        fixture.status = response.status.as_u16();
+        fixture.content_type = response
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        match serde_json::from_slice::<Value>(&response.body) {
+            Ok(mut json) => {
+                self.scrubber.scrub(&mut json);
+                fixture.body = Some(json);
+            }
+            Err(_) => fixture.body_base64 = Some(STANDARD.encode(&response.body)),
+        }
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let contents = serde_json::to_vec_pretty(&fixture)
+            .map_err(|e| DnevnikError::encode(&fixture.path, e))?;
+        tokio::fs::write(self.dir.join(name), contents).await?;
+        Ok(response)
+    }
+}
+
+/// Transport serving responses from the fixtures. Missing fixtures result in 404 responses
+#[derive(Debug, Clone)]
+pub struct ReplayTransport {
+    dir: PathBuf,
+}
+
+impl ReplayTransport {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let name = fixture_name(&request);
+        let contents = match tokio::fs::read(self.dir.join(&name)).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(HttpResponse::new(
+                    StatusCode::NOT_FOUND,
+                    format!("no fixture {}", name),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let fixture: Fixture = serde_json::from_slice(&contents)
+            .map_err(|e| DnevnikError::decode(&name, &contents, e))?;
+        fixture.into_response()
+    }
+}
+
+/// Recording transport when the [RECORD_ENV] variable is set, replaying transport otherwise
+pub fn from_env<P: Into<PathBuf>>(dir: P) -> Result<Arc<dyn Transport>> {
+    if std::env::var_os(RECORD_ENV).is_some() {
+        let inner = ReqwestTransport::new(Duration::from_secs(30))?;
+        Ok(Arc::new(RecordingTransport::new(inner, dir)))
+    } else {
+        Ok(Arc::new(ReplayTransport::new(dir)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+    use crate::transport::MemoryTransport;
+    use reqwest::Url;
+
+    #[tokio::test]
+    async fn test_record_and_replay() -> anyhow::Result<()> {
+        let dir = TempDir::new("cassette");
+        let inner = MemoryTransport::new().json(
+            "/mobile/api/profile",
+            &serde_json::json!({
+                "profile": {"first_name": "Иван", "snils": "123-456-789 00", "id": 1},
+                "children": [{"contingent_guid": "9b6a1c3e-0000-4000-8000-000000000001", "phone": null}]
+            }),
+        );
+        let request = HttpRequest::get(Url::parse("https://dnevnik.mos.ru/mobile/api/profile")?)
+            .query(&[("b", 2)])
+            .query(&[("a", 1)]);
+        assert_eq!(
+            fixture_name(&request),
+            "get_mobile_api_profile@a=1&b=2.json"
+        );
+
+        let recorded = RecordingTransport::new(inner, dir.path())
+            .send(request.clone())
+            .await?;
+        let real: Value = serde_json::from_slice(&recorded.body)?;
+        assert_eq!(real["profile"]["first_name"], "Иван");
+
+        let replayed = ReplayTransport::new(dir.path()).send(request).await?;
+        let scrubbed: Value = serde_json::from_slice(&replayed.body)?;
+        assert_eq!(scrubbed["profile"]["first_name"], "Имя");
+        assert_eq!(scrubbed["profile"]["snils"], "000-000-000 00");
+        assert_eq!(scrubbed["profile"]["id"], 1);
+        assert_eq!(
+            scrubbed["children"][0]["contingent_guid"],
+            "00000000-0000-0000-0000-000000000000"
+        );
+        assert!(scrubbed["children"][0]["phone"].is_null());
+        Ok(())
+    }
+}
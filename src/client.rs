@@ -0,0 +1,429 @@
+//! Request pipeline shared by the diary clients
+
+use crate::auth::AuthToken;
+use crate::cache::{Cache, CacheEntry};
+use crate::config::{DiaryConfig, Endpoint};
+use crate::error::{DnevnikError, Result};
+use crate::offline::{is_outage, Offline, OfflineMode};
+use crate::retry::RateLimiter;
+use crate::token::TokenProvider;
+use crate::transport::{HttpRequest, HttpResponse, Transport};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use reqwest::header::{
+    HeaderMap, HeaderValue, AUTHORIZATION, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    REFERER, USER_AGENT,
+};
+use reqwest::{Method, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Api {
+    pub transport: Arc<dyn Transport>,
+    pub config: Arc<DiaryConfig>,
+    pub tokens: Arc<dyn TokenProvider>,
+    pub limiter: Option<Arc<RateLimiter>>,
+    /// ID of the account the client is connected to, once its profile is fetched
+    account: Option<u64>,
+    default_headers: HeaderMap,
+    /// Sync time of the oldest response served from the offline snapshot
+    stale_since: Arc<Mutex<Option<DateTime<Utc>>>>,
+}
+
+impl Api {
+    #[allow(clippy::option_env_unwrap)]
+    pub fn new(
+        config: DiaryConfig,
+        tokens: Arc<dyn TokenProvider>,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self> {
+        parse_url(&config.base_url)?;
+        let mut default_headers = HeaderMap::new();
+        default_headers.append(
+            USER_AGENT,
+            HeaderValue::from_str(&format!(
+                "Dnevnik-Mos-Rust/{}",
+                option_env!("CARGO_PKG_VERSION").unwrap()
+            ))?,
+        );
+        default_headers.append(REFERER, HeaderValue::from_str(&config.referer())?);
+        Ok(Self {
+            transport,
+            limiter: config
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+            config: Arc::new(config),
+            tokens,
+            account: None,
+            default_headers,
+            stale_since: Arc::default(),
+        })
+    }
+
+    /// Copy of this client storing the responses under the account with the provided ID
+    pub fn for_account(self, account: u64) -> Self {
+        Self {
+            account: Some(account),
+            ..self
+        }
+    }
+
+    /// Key of the response to a request in the cache and the offline snapshot. Responses
+    /// are namespaced by the account, or by a fingerprint of the token until the profile is
+    /// fetched, so that clients of different accounts can share a store
+    async fn store_key(&self, url: &Url) -> Result<String> {
+        let owner = match self.account {
+            Some(account) => account.to_string(),
+            None => fingerprint(&self.tokens.token().await?.token),
+        };
+        Ok(format!("{}#{}", url, owner))
+    }
+
+    pub fn endpoint(&self, endpoint: Endpoint) -> Result<Url> {
+        parse_url(&self.config.endpoint_url(endpoint))
+    }
+
+    /// Copy of this client tracking the staleness of its responses separately
+    pub fn tracked(&self) -> Self {
+        Self {
+            stale_since: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// Sync time of the oldest response served from the offline snapshot by this client
+    pub fn stale_since(&self) -> Option<DateTime<Utc>> {
+        *self.stale_since.lock().unwrap()
+    }
+
+    /// Sends the request, saving the response to the offline snapshot or serving it from
+    /// the snapshot according to the offline mode
+    pub async fn execute(&self, request: HttpRequest) -> Result<(String, Bytes)> {
+        let offline = match &self.config.offline {
+            Some(offline) if request.method == Method::GET => offline,
+            _ => {
+                let (endpoint, body, _) = self.execute_online(request).await?;
+                return Ok((endpoint, body));
+            }
+        };
+        let key = self.store_key(&request.url).await?;
+        let endpoint = request.endpoint();
+        if offline.mode == OfflineMode::Always {
+            return self.serve_snapshot(offline, endpoint, &key).await;
+        }
+        match self.execute_online(request).await {
+            Ok((endpoint, body, fetched_at)) => {
+                offline.save(&key, body.clone(), fetched_at).await;
+                Ok((endpoint, body))
+            }
+            Err(error) if is_outage(&error) => {
+                log::warn!("{}, falling back to the offline snapshot", error);
+                self.serve_snapshot(offline, endpoint, &key)
+                    .await
+                    .map_err(|_| error)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn serve_snapshot(
+        &self,
+        offline: &Offline,
+        endpoint: String,
+        key: &str,
+    ) -> Result<(String, Bytes)> {
+        let entry = offline.load(&endpoint, key).await?;
+        let mut stale_since = self.stale_since.lock().unwrap();
+        match *stale_since {
+            Some(since) if since <= entry.stored_at => {}
+            _ => *stale_since = Some(entry.stored_at),
+        }
+        Ok((endpoint, entry.body))
+    }
+
+    /// Sends the request without the offline snapshot, for downloads that don't belong in it
+    pub async fn download(&self, request: HttpRequest) -> Result<Bytes> {
+        let (_, body, _) = self.execute_online(request).await?;
+        Ok(body)
+    }
+
+    /// Sends the request, serving it from the cache when possible. Returns the body along
+    /// with the time it was received from the server
+    async fn execute_online(&self, request: HttpRequest) -> Result<(String, Bytes, DateTime<Utc>)> {
+        let cached = self
+            .config
+            .cache
+            .as_ref()
+            .filter(|_| request.method == Method::GET)
+            .and_then(|cache| {
+                cache
+                    .ttl_for(&self.config, &request.url)
+                    .map(|ttl| (cache, ttl))
+            });
+        match cached {
+            Some((cache, ttl)) => self.execute_cached(request, cache, ttl).await,
+            None => {
+                let (endpoint, response) = self.execute_retrying(request).await?;
+                Ok((endpoint, response.body, Utc::now()))
+            }
+        }
+    }
+
+    /// Serves the request from a fresh cache entry, otherwise sends it, revalidating
+    /// the expired entry if possible, and stores the response
+    async fn execute_cached(
+        &self,
+        mut request: HttpRequest,
+        cache: &Cache,
+        ttl: Duration,
+    ) -> Result<(String, Bytes, DateTime<Utc>)> {
+        let key = self.store_key(&request.url).await?;
+        let entry = cache.store().get(&key).await.unwrap_or_else(|e| {
+            log::warn!("Could not read cached response of {}: {}", key, e);
+            None
+        });
+        if let Some(entry) = &entry {
+            if !entry.is_expired(ttl) {
+                return Ok((request.endpoint(), entry.body.clone(), entry.stored_at));
+            }
+            if let Some(etag) = &entry.etag {
+                request
+                    .headers
+                    .insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request
+                    .headers
+                    .insert(IF_MODIFIED_SINCE, HeaderValue::from_str(last_modified)?);
+            }
+        }
+        let (endpoint, response) = self.execute_retrying(request).await?;
+        let header = |name| {
+            response
+                .headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let fresh = match entry {
+            Some(entry) if response.status == StatusCode::NOT_MODIFIED => CacheEntry {
+                stored_at: Utc::now(),
+                ..entry
+            },
+            _ => CacheEntry {
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+                body: response.body.clone(),
+                stored_at: Utc::now(),
+            },
+        };
+        if let Err(e) = cache.store().put(&key, fresh.clone()).await {
+            log::warn!("Could not cache response of {}: {}", key, e);
+        }
+        Ok((endpoint, fresh.body, fresh.stored_at))
+    }
+
+    /// Removes cached responses of the endpoint, or all cached responses if it is `None`
+    pub async fn invalidate(&self, endpoint: Option<Endpoint>) -> Result<()> {
+        match &self.config.cache {
+            Some(cache) => {
+                let prefix = endpoint
+                    .map(|endpoint| self.config.endpoint_url(endpoint))
+                    .unwrap_or_default();
+                cache.store().remove(&prefix).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the request, retrying it on temporary failures according to the retry policy
+    async fn execute_retrying(&self, request: HttpRequest) -> Result<(String, HttpResponse)> {
+        let policy = &self.config.retry;
+        let mut attempt = 1;
+        loop {
+            match self.execute_authorized(request.clone()).await {
+                Err(error) if attempt < policy.max_attempts && policy.is_retryable(&error) => {
+                    let delay = policy.delay(attempt);
+                    log::debug!(
+                        "Attempt {} failed ({}), retrying in {:?}",
+                        attempt,
+                        error,
+                        delay
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                other => return other,
+            }
+        }
+    }
+
+    /// Sends the request authorized with the current token, refreshing the token and
+    /// retrying once if the server rejects it
+    async fn execute_authorized(&self, request: HttpRequest) -> Result<(String, HttpResponse)> {
+        let token = self.tokens.token().await?;
+        match self.send(self.authorize(request.clone(), &token)?).await {
+            Err(error) if error.is_unauthorized() => {
+                let fresh = match self.tokens.refresh(&token).await {
+                    Ok(fresh) => fresh,
+                    Err(refresh_error) => {
+                        log::warn!("Could not refresh the auth token: {}", refresh_error);
+                        return Err(error);
+                    }
+                };
+                self.send(self.authorize(request, &fresh)?).await
+            }
+            other => other,
+        }
+    }
+
+    fn authorize(&self, mut request: HttpRequest, token: &AuthToken) -> Result<HttpRequest> {
+        let value = HeaderValue::from_str(&token.token)?;
+        for (name, default) in &self.default_headers {
+            if !request.headers.contains_key(name) {
+                request.headers.insert(name, default.clone());
+            }
+        }
+        request.headers.insert("Auth-Token", value.clone());
+        request.headers.insert(AUTHORIZATION, value);
+        Ok(request)
+    }
+
+    /// Sends the request, returning path of the requested endpoint and the response.
+    /// `304 Not Modified` responses to conditional requests are not treated as errors.
+    /// Every request, including the retries, waits for the rate limiter
+    async fn send(&self, request: HttpRequest) -> Result<(String, HttpResponse)> {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+        let endpoint = request.endpoint();
+        let response = self.transport.send(request).await?;
+        if !response.status.is_success() && response.status != StatusCode::NOT_MODIFIED {
+            return Err(DnevnikError::from_status(
+                &endpoint,
+                response.status,
+                &response.body,
+            ));
+        }
+        Ok((endpoint, response))
+    }
+
+    pub async fn fetch<T: DeserializeOwned>(&self, request: HttpRequest) -> Result<T> {
+        let (endpoint, body) = self.execute(request).await?;
+        serde_json::from_slice(&body).map_err(|e| DnevnikError::decode(&endpoint, &body, e))
+    }
+}
+
+/// FNV-1a hash of the token, stable between runs for the disk stores
+fn fingerprint(token: &str) -> String {
+    let hash = token.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    });
+    format!("token-{:016x}", hash)
+}
+
+pub(crate) fn parse_url(url: &str) -> Result<Url> {
+    Url::from_str(url).map_err(|e| DnevnikError::InvalidUrl {
+        url: url.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::{RateLimit, RetryPolicy};
+    use crate::testing::{MockResponse, MockServer};
+    use crate::token::{RefreshingToken, StaticToken};
+    use crate::transport::ReqwestTransport;
+    use tokio::time::Instant;
+
+    async fn server() -> MockServer {
+        MockServer::start(|req| match req.header("auth-token") {
+            Some("fresh") => MockResponse::new(200, r#"{"ok":true}"#),
+            _ => MockResponse::new(401, r#"{"message":"token expired"}"#),
+        })
+        .await
+    }
+
+    fn api(server: &MockServer, tokens: Arc<dyn TokenProvider>) -> Api {
+        let config = DiaryConfig {
+            base_url: server.url(""),
+            ..Default::default()
+        };
+        let transport = ReqwestTransport::new(config.timeout).unwrap();
+        Api::new(config, tokens, Arc::new(transport)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_refresh() -> anyhow::Result<()> {
+        let server = server().await;
+        let tokens = RefreshingToken::new(AuthToken::new("stale"), |_| async {
+            Ok(AuthToken::new("fresh"))
+        });
+        let mut api = api(&server, Arc::new(tokens));
+        api.limiter = Some(Arc::new(RateLimiter::new(RateLimit {
+            requests_per_second: 5.0,
+            burst: 1,
+        })));
+        let start = Instant::now();
+        let value: serde_json::Value = api
+            .fetch(HttpRequest::get(api.endpoint(Endpoint::Profile)?))
+            .await?;
+        assert_eq!(value["ok"], true);
+        assert_eq!(server.requests().len(), 2);
+        // the retry with the fresh token waits for the limiter too
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_temporary_failures() -> anyhow::Result<()> {
+        let failures = std::sync::atomic::AtomicUsize::new(2);
+        let server = MockServer::start(move |_| {
+            let left = failures.load(std::sync::atomic::Ordering::SeqCst);
+            if left > 0 {
+                failures.store(left - 1, std::sync::atomic::Ordering::SeqCst);
+                MockResponse::new(503, "Service Unavailable")
+            } else {
+                MockResponse::new(200, "[]")
+            }
+        })
+        .await;
+        let config = DiaryConfig {
+            base_url: server.url(""),
+            retry: RetryPolicy {
+                base_delay: std::time::Duration::from_millis(1),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let transport = Arc::new(ReqwestTransport::new(config.timeout)?);
+        let api = Api::new(
+            config,
+            Arc::new(StaticToken(AuthToken::new("token"))),
+            transport,
+        )?;
+        let years: Vec<serde_json::Value> = api
+            .fetch(HttpRequest::get(api.endpoint(Endpoint::AcademicYears)?))
+            .await?;
+        assert!(years.is_empty());
+        assert_eq!(server.requests().len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_static_token_unauthorized() -> anyhow::Result<()> {
+        let server = server().await;
+        let api = api(&server, Arc::new(StaticToken(AuthToken::new("stale"))));
+        let error = api
+            .fetch::<serde_json::Value>(HttpRequest::get(api.endpoint(Endpoint::Profile)?))
+            .await
+            .unwrap_err();
+        assert!(error.is_unauthorized());
+        Ok(())
+    }
+}
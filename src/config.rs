@@ -0,0 +1,168 @@
+//! Configuration of the [Diary](crate::diary::Diary) client: base host, endpoint paths and timeouts
+
+use crate::cache::Cache;
+use crate::diary::{CORE_API, GLOBAL_DMR_URL, JERSEY_API, LMS_API, MOBILE_API, REPORTS_API};
+use crate::offline::Offline;
+use crate::retry::{RateLimit, RetryPolicy};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// All API endpoints known to the diary client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Endpoint {
+    /// Profile of the current account
+    Profile,
+    /// Session information of the current account
+    Sessions,
+    /// List of all academic years
+    AcademicYears,
+    /// Daily schedule of a student
+    Schedule,
+    /// Year marks for an academic year
+    FinalMarksPrevYear,
+    /// Lesson plans with modules and topics
+    LessonPlans,
+    /// Single schedule item, the lesson ID is appended to this path
+    LessonScheduleItems,
+    /// Homework of a student
+    StudentHomeworks,
+    /// Progress report with average grades
+    Progress,
+    /// Visits to the school building
+    Visits,
+    /// Lessons scheduled for a teacher or a group
+    ScheduleItems,
+    /// Student profiles of a class unit or a group
+    StudentProfiles,
+    /// Marks issued by a teacher or to a group
+    Marks,
+    /// Subject groups taught by a teacher
+    Groups,
+}
+
+impl Endpoint {
+    /// Path of this endpoint on the default dnevnik.mos.ru host
+    pub fn default_path(&self) -> String {
+        match self {
+            Endpoint::Profile => format!("{}/profile", MOBILE_API),
+            Endpoint::Sessions => format!("{}/sessions", LMS_API),
+            Endpoint::AcademicYears => format!("{}/academic_years", CORE_API),
+            Endpoint::Schedule => format!("{}/schedule", MOBILE_API),
+            Endpoint::FinalMarksPrevYear => format!("{}/final_marks_prev_year", CORE_API),
+            Endpoint::LessonPlans => format!("{}/lesson_plans", JERSEY_API),
+            Endpoint::LessonScheduleItems => format!("{}/lesson_schedule_items", MOBILE_API),
+            Endpoint::StudentHomeworks => format!("{}/student_homeworks", CORE_API),
+            Endpoint::Progress => format!("{}/progress/json", REPORTS_API),
+            Endpoint::Visits => format!("{}/visits", MOBILE_API),
+            Endpoint::ScheduleItems => format!("{}/schedule_items", CORE_API),
+            Endpoint::StudentProfiles => format!("{}/student_profiles", CORE_API),
+            Endpoint::Marks => format!("{}/marks", CORE_API),
+            Endpoint::Groups => format!("{}/groups", CORE_API),
+        }
+    }
+}
+
+/// Configuration of a diary client
+#[derive(Debug, Clone)]
+pub struct DiaryConfig {
+    /// Base URL of the host, without the trailing slash, e.g. `https://dnevnik.mos.ru`
+    pub base_url: String,
+    /// Value of the `Referer` header. `None` means `{base_url}/diary/`
+    pub referer: Option<String>,
+    /// Timeout for regular API requests
+    pub timeout: Duration,
+    /// Timeout for attachment downloads
+    pub download_timeout: Duration,
+    /// Policy for retrying requests that failed temporarily
+    pub retry: RetryPolicy,
+    /// Client-side limit of the request rate, shared by all clones of the diary. `None` means unlimited
+    pub rate_limit: Option<RateLimit>,
+    /// Overridden endpoint paths. Values starting with `http://` or `https://`
+    /// are used as is, other values are appended to the [base_url](DiaryConfig::base_url)
+    pub endpoints: HashMap<Endpoint, String>,
+    /// Cache of the responses. `None` disables caching
+    pub cache: Option<Cache>,
+    /// Offline snapshot of the responses. `None` disables offline mode
+    pub offline: Option<Offline>,
+}
+
+impl Default for DiaryConfig {
+    fn default() -> Self {
+        Self {
+            base_url: GLOBAL_DMR_URL.to_string(),
+            referer: None,
+            timeout: Duration::from_secs(10),
+            download_timeout: Duration::from_secs(15),
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            endpoints: HashMap::new(),
+            cache: None,
+            offline: None,
+        }
+    }
+}
+
+impl DiaryConfig {
+    /// Gets the path of the provided endpoint, taking overrides into account
+    pub fn endpoint_path(&self, endpoint: Endpoint) -> String {
+        self.endpoints
+            .get(&endpoint)
+            .cloned()
+            .unwrap_or_else(|| endpoint.default_path())
+    }
+
+    /// Gets the full URL string of the provided endpoint
+    pub fn endpoint_url(&self, endpoint: Endpoint) -> String {
+        self.resolve(&self.endpoint_path(endpoint))
+    }
+
+    /// Resolves a path relative to the [base_url](DiaryConfig::base_url)
+    pub fn resolve(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else {
+            format!("{}{}", self.base_url.trim_end_matches('/'), path)
+        }
+    }
+
+    /// Gets the value of the `Referer` header
+    pub fn referer(&self) -> String {
+        self.referer
+            .clone()
+            .unwrap_or_else(|| format!("{}/diary/", self.base_url.trim_end_matches('/')))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endpoint_resolution() {
+        let mut config = DiaryConfig {
+            base_url: "http://localhost:8080/".to_string(),
+            ..Default::default()
+        };
+        config
+            .endpoints
+            .insert(Endpoint::Visits, "/v2/visits".to_string());
+        config.endpoints.insert(
+            Endpoint::Profile,
+            "https://school.mos.ru/api/profile".to_string(),
+        );
+        assert_eq!(
+            config.endpoint_url(Endpoint::Schedule),
+            "http://localhost:8080/mobile/api/schedule"
+        );
+        assert_eq!(
+            config.endpoint_url(Endpoint::Visits),
+            "http://localhost:8080/v2/visits"
+        );
+        assert_eq!(
+            config.endpoint_url(Endpoint::Profile),
+            "https://school.mos.ru/api/profile"
+        );
+        assert_eq!(config.referer(), "http://localhost:8080/diary/");
+    }
+}
@@ -1,20 +1,32 @@
 //! Main module of this crate, allowing access to the diary
 
+use crate::auth::AuthToken;
+use crate::cache::Cache;
+use crate::client::{parse_url, Api};
+use crate::config::{DiaryConfig, Endpoint};
+use crate::error::{DnevnikError, Result};
 use crate::model::attendance::{Payload, StudentAttendance};
 use crate::model::hw::{HomeworkAttachment, StudentHomework};
 use crate::model::lessons::{
     AcademicYear, FinalMark, LessonInstance, LessonPlan, LessonScheduleItem, Schedule,
 };
 use crate::model::marks::GlobalAverageGrade;
-use crate::model::{StudentProfile, StudentSession};
-use anyhow::bail;
-use chrono::{DateTime, Utc};
-use lazy_static::lazy_static;
-use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, REFERER, USER_AGENT};
-use reqwest::{Client, ClientBuilder, Url};
+use crate::model::{ProfileType, StudentDetails, StudentProfile, StudentSession};
+use crate::offline::{Offline, Snapshot};
+use crate::retry::{RateLimit, RetryPolicy};
+use crate::teacher::TeacherDiary;
+use crate::token::{StaticToken, TokenProvider};
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use futures::stream::{self, Stream, StreamExt};
+use reqwest::header::ACCEPT;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use std::future::Future;
 use std::io::Cursor;
 use std::path::PathBuf;
-use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
 pub const GLOBAL_DMR_URL: &str = "https://dnevnik.mos.ru";
@@ -24,30 +36,241 @@ pub const LMS_API: &str = "/lms/api";
 pub const JERSEY_API: &str = "/jersey/api";
 pub const REPORTS_API: &str = "/jersey/api";
 
-lazy_static! {
-    pub static ref PROFILE_ENDPOINT: String = format!("{}{}/profile", GLOBAL_DMR_URL, MOBILE_API);
-    pub static ref SESSIONS_ENDPOINT: String = format!("{}{}/sessions", GLOBAL_DMR_URL, LMS_API);
-    pub static ref ACADEMIC_YEARS_ENDPOINT: String =
-        format!("{}{}/academic_years", GLOBAL_DMR_URL, CORE_API);
-    pub static ref SCHEDULE_ENDPOINT: String = format!("{}{}/schedule", GLOBAL_DMR_URL, MOBILE_API);
-    pub static ref FINAL_MARKS_PREV_YEAR_ENDPOINT: String =
-        format!("{}{}/final_marks_prev_year", GLOBAL_DMR_URL, CORE_API);
-    pub static ref LESSON_PLANS_ENDPOINT: String =
-        format!("{}{}/lesson_plans", GLOBAL_DMR_URL, JERSEY_API);
-    pub static ref STUDENT_HOMEWORKS_ENDPOINT: String =
-        format!("{}{}/student_homeworks", GLOBAL_DMR_URL, CORE_API);
-    pub static ref PROGRESS_ENDPOINT: String =
-        format!("{}{}/progress/json", GLOBAL_DMR_URL, REPORTS_API);
-    pub static ref VISITS_ENDPOINT: String = format!("{}{}/visits", GLOBAL_DMR_URL, MOBILE_API);
+/// Default URL of an endpoint, kept for the endpoint statics
+fn default_url(endpoint: Endpoint) -> String {
+    format!("{}{}", GLOBAL_DMR_URL, endpoint.default_path())
+}
+
+#[allow(deprecated)]
+pub use endpoints::{
+    ACADEMIC_YEARS_ENDPOINT, FINAL_MARKS_PREV_YEAR_ENDPOINT, LESSON_PLANS_ENDPOINT,
+    PROFILE_ENDPOINT, PROGRESS_ENDPOINT, SCHEDULE_ENDPOINT, SESSIONS_ENDPOINT,
+    STUDENT_HOMEWORKS_ENDPOINT, VISITS_ENDPOINT,
+};
+
+/// Default URLs of the endpoints, superseded by [Endpoint] and [DiaryConfig]
+mod endpoints {
+    #![allow(deprecated)]
+
+    use super::default_url;
+    use crate::config::Endpoint;
+    use lazy_static::lazy_static;
+
+    lazy_static! {
+        #[deprecated(note = "use `Endpoint::Profile` with `DiaryConfig`")]
+        pub static ref PROFILE_ENDPOINT: String = default_url(Endpoint::Profile);
+        #[deprecated(note = "use `Endpoint::Sessions` with `DiaryConfig`")]
+        pub static ref SESSIONS_ENDPOINT: String = default_url(Endpoint::Sessions);
+        #[deprecated(note = "use `Endpoint::AcademicYears` with `DiaryConfig`")]
+        pub static ref ACADEMIC_YEARS_ENDPOINT: String = default_url(Endpoint::AcademicYears);
+        #[deprecated(note = "use `Endpoint::Schedule` with `DiaryConfig`")]
+        pub static ref SCHEDULE_ENDPOINT: String = default_url(Endpoint::Schedule);
+        #[deprecated(note = "use `Endpoint::FinalMarksPrevYear` with `DiaryConfig`")]
+        pub static ref FINAL_MARKS_PREV_YEAR_ENDPOINT: String =
+            default_url(Endpoint::FinalMarksPrevYear);
+        #[deprecated(note = "use `Endpoint::LessonPlans` with `DiaryConfig`")]
+        pub static ref LESSON_PLANS_ENDPOINT: String = default_url(Endpoint::LessonPlans);
+        #[deprecated(note = "use `Endpoint::StudentHomeworks` with `DiaryConfig`")]
+        pub static ref STUDENT_HOMEWORKS_ENDPOINT: String = default_url(Endpoint::StudentHomeworks);
+        #[deprecated(note = "use `Endpoint::Progress` with `DiaryConfig`")]
+        pub static ref PROGRESS_ENDPOINT: String = default_url(Endpoint::Progress);
+        #[deprecated(note = "use `Endpoint::Visits` with `DiaryConfig`")]
+        pub static ref VISITS_ENDPOINT: String = default_url(Endpoint::Visits);
+    }
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone)]
 pub struct Diary {
-    client: Client,
-    auth_token: String,
+    api: Api,
     pub profile: StudentProfile,
     student_id: u64,
+    /// Index of the selected child in the profile
+    child: Option<usize>,
+}
+
+/// Builder for the [Diary], allowing to point the client to a different host,
+/// override individual endpoint paths or provide refreshable tokens
+#[derive(Debug, Clone)]
+pub struct DiaryBuilder {
+    tokens: Arc<dyn TokenProvider>,
+    transport: Option<Arc<dyn Transport>>,
+    child_id: Option<u64>,
+    config: DiaryConfig,
+}
+
+impl DiaryBuilder {
+    pub fn new<S: Into<String>>(token: S) -> Self {
+        Self::with_provider(StaticToken(AuthToken::new(token)))
+    }
+
+    /// Creates a builder that consults the provided token provider before each request
+    pub fn with_provider<P: TokenProvider + 'static>(provider: P) -> Self {
+        Self {
+            tokens: Arc::new(provider),
+            transport: None,
+            child_id: None,
+            config: DiaryConfig::default(),
+        }
+    }
+
+    /// Replaces the whole configuration of this builder
+    pub fn config(mut self, config: DiaryConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the base URL of the host, e.g. `https://school.mos.ru`
+    pub fn base_url<S: Into<String>>(mut self, base_url: S) -> Self {
+        self.config.base_url = base_url.into();
+        self
+    }
+
+    /// Sets the value of the `Referer` header
+    pub fn referer<S: Into<String>>(mut self, referer: S) -> Self {
+        self.config.referer = Some(referer.into());
+        self
+    }
+
+    /// Sets the timeout for regular API requests
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for attachment downloads
+    pub fn download_timeout(mut self, timeout: Duration) -> Self {
+        self.config.download_timeout = timeout;
+        self
+    }
+
+    /// Sets the policy for retrying requests that failed temporarily
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.config.retry = policy;
+        self
+    }
+
+    /// Limits the rate of requests sent by the diary and all of its clones.
+    /// Rates below one request a minute are raised to it
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.config.rate_limit = Some(limit.clamped());
+        self
+    }
+
+    /// Sets the transport used to send requests. Defaults to [ReqwestTransport]
+    pub fn transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Selects the child with the provided account ID. Defaults to the first child of the profile
+    pub fn child(mut self, child_id: u64) -> Self {
+        self.child_id = Some(child_id);
+        self
+    }
+
+    /// Enables caching of the responses
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.config.cache = Some(cache);
+        self
+    }
+
+    /// Enables the offline mode, saving responses to a snapshot store and serving them from it
+    pub fn offline(mut self, offline: Offline) -> Self {
+        self.config.offline = Some(offline);
+        self
+    }
+
+    /// Overrides path of a single endpoint. Absolute URLs are used as is.
+    pub fn endpoint<S: Into<String>>(mut self, endpoint: Endpoint, path: S) -> Self {
+        self.config.endpoints.insert(endpoint, path.into());
+        self
+    }
+
+    /// Builds the diary, fetching the profile of the account
+    pub async fn build(self) -> Result<Diary> {
+        let child_id = self.child_id;
+        let (api, profile) = self.connect().await?;
+        let mut diary = Diary {
+            api,
+            student_id: profile.account.id,
+            profile,
+            child: None,
+        };
+        match child_id {
+            Some(child_id) => diary.select_child(child_id)?,
+            None if !diary.profile.children().is_empty() => diary.select(0),
+            None => {}
+        }
+        Ok(diary)
+    }
+
+    /// Builds the teacher-side client, fetching the profile of the account.
+    /// Fails with [DnevnikError::WrongProfileType] for non-teacher accounts
+    pub async fn build_teacher(self) -> Result<TeacherDiary> {
+        let (api, profile) = self.connect().await?;
+        if !profile.is_teacher() {
+            return Err(DnevnikError::WrongProfileType {
+                expected: ProfileType::Teacher.as_str(),
+                actual: profile.account.profile_type,
+            });
+        }
+        Ok(TeacherDiary::new(api, profile))
+    }
+
+    async fn connect(self) -> Result<(Api, StudentProfile)> {
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => Arc::new(ReqwestTransport::new(self.config.timeout)?),
+        };
+        let api = Api::new(self.config, self.tokens, transport)?;
+        let profile: StudentProfile = api
+            .fetch(HttpRequest::get(api.endpoint(Endpoint::Profile)?))
+            .await?;
+        Ok((api.for_account(profile.account.id), profile))
+    }
+}
+
+/// Options of [Diary::schedule_range]
+#[derive(Debug, Clone)]
+pub struct RangeOptions {
+    /// Maximum amount of days fetched at the same time
+    pub concurrency: usize,
+    /// Whether Saturdays and Sundays are skipped
+    pub skip_weekends: bool,
+    /// Days that are skipped, e.g. public holidays and vacations
+    pub holidays: HashSet<NaiveDate>,
+}
+
+impl Default for RangeOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            skip_weekends: false,
+            holidays: HashSet::new(),
+        }
+    }
+}
+
+impl RangeOptions {
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    pub fn skip_weekends(mut self, skip: bool) -> Self {
+        self.skip_weekends = skip;
+        self
+    }
+
+    pub fn holidays<I: IntoIterator<Item = NaiveDate>>(mut self, holidays: I) -> Self {
+        self.holidays.extend(holidays);
+        self
+    }
+
+    fn skips(&self, date: &NaiveDate) -> bool {
+        let weekend = matches!(date.weekday(), Weekday::Sat | Weekday::Sun);
+        (self.skip_weekends && weekend) || self.holidays.contains(date)
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -56,219 +279,369 @@ struct StudentAuth {
 }
 
 impl Diary {
-    #[allow(clippy::option_env_unwrap)]
-    pub async fn new<S: Into<String>>(token: S) -> anyhow::Result<Self> {
-        let str_token = token.into();
-        let mut default_headers = HeaderMap::new();
-        default_headers.append(
-            USER_AGENT,
-            HeaderValue::from_str(&format!(
-                "Dnevnik-Mos-Rust/{}",
-                option_env!("CARGO_PKG_VERSION").unwrap()
-            ))?,
-        );
-        default_headers.append(
-            REFERER,
-            HeaderValue::from_str("https://dnevnik.mos.ru/diary/")?,
-        );
-        default_headers.append("Auth-Token", HeaderValue::from_str(&str_token)?);
-        default_headers.append(AUTHORIZATION, HeaderValue::from_str(&str_token)?);
-        let client = ClientBuilder::new()
-            .default_headers(default_headers)
-            .timeout(Duration::from_secs(10))
-            .build()?;
-        let profile: StudentProfile = client
-            .get(Url::from_str(&PROFILE_ENDPOINT)?)
-            .send()
-            .await?
-            .json()
-            .await?;
-        Ok(Self {
-            client,
-            auth_token: str_token,
-            student_id: profile.account.id,
-            profile,
+    pub async fn new<S: Into<String>>(token: S) -> Result<Self> {
+        DiaryBuilder::new(token).build().await
+    }
+
+    /// Creates a builder for a diary with custom configuration
+    pub fn builder<S: Into<String>>(token: S) -> DiaryBuilder {
+        DiaryBuilder::new(token)
+    }
+
+    /// Configuration of this diary
+    pub fn config(&self) -> &DiaryConfig {
+        &self.api.config
+    }
+
+    /// Token that will be used for the next request, along with its expiry
+    pub async fn token(&self) -> Result<AuthToken> {
+        self.api.tokens.token().await
+    }
+
+    /// Details of all students available to this account
+    pub fn children(&self) -> &[StudentDetails] {
+        self.profile.children()
+    }
+
+    /// Details of the student whose data is requested by this diary
+    pub fn child(&self) -> Option<&StudentDetails> {
+        self.child.map(|index| &self.profile.children()[index])
+    }
+
+    /// ID of the student whose data is requested by this diary
+    pub fn student_id(&self) -> u64 {
+        self.student_id
+    }
+
+    /// Selects the child whose data is requested by this diary
+    pub fn select_child(&mut self, child_id: u64) -> Result<()> {
+        let index = self
+            .profile
+            .children()
+            .iter()
+            .position(|child| child.parent_account.id == child_id)
+            .ok_or_else(|| {
+                DnevnikError::missing("child", format!("profile children (ID {})", child_id))
+            })?;
+        self.select(index);
+        Ok(())
+    }
+
+    /// Creates a handle for the child with the provided account ID, sharing the client with this diary
+    pub fn for_child(&self, child_id: u64) -> Result<Diary> {
+        let mut diary = self.clone();
+        diary.select_child(child_id)?;
+        Ok(diary)
+    }
+
+    fn select(&mut self, index: usize) {
+        self.child = Some(index);
+        // student accounts request their own data, representatives request data of the child
+        self.student_id = if self.profile.is_representative() {
+            self.profile.children()[index].parent_account.id
+        } else {
+            self.profile.account.id
+        };
+    }
+
+    /// Removes cached responses of the endpoint, so they are downloaded again
+    pub async fn invalidate(&self, endpoint: Endpoint) -> Result<()> {
+        self.api.invalidate(Some(endpoint)).await
+    }
+
+    /// Removes all cached responses
+    pub async fn clear_cache(&self) -> Result<()> {
+        self.api.invalidate(None).await
+    }
+
+    /// Runs the provided requests on a copy of this diary, reporting whether any of
+    /// the data was served from the offline snapshot and how old it is
+    pub async fn snapshot<T, F, Fut>(&self, requests: F) -> Result<Snapshot<T>>
+    where
+        F: FnOnce(Diary) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut diary = self.clone();
+        diary.api = self.api.tracked();
+        let api = diary.api.clone();
+        let data = requests(diary).await?;
+        Ok(Snapshot {
+            data,
+            synced_at: api.stale_since(),
         })
     }
 
-    pub async fn session(&self) -> anyhow::Result<StudentSession> {
-        self.client
-            .post(Url::from_str(&SESSIONS_ENDPOINT)?)
-            .json(&StudentAuth {
-                auth_token: self.auth_token.clone(),
-            })
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(anyhow::Error::from)
+    /// Fetches the data used offline for the provided period, saving it to the offline snapshot
+    pub async fn sync(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<()> {
+        for (_, schedule) in self.schedule_range(from, to, RangeOptions::default()).await {
+            schedule?;
+        }
+        self.homework(from, to).await?;
+        self.progress().await?;
+        if self.child().and_then(|child| child.contract_id).is_some() {
+            self.visits(from, to).await?;
+        }
+        Ok(())
     }
 
-    pub async fn academic_years(&self) -> anyhow::Result<Vec<AcademicYear>> {
-        self.client
-            .get(Url::from_str(&ACADEMIC_YEARS_ENDPOINT)?)
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(anyhow::Error::from)
+    fn endpoint(&self, endpoint: Endpoint) -> Result<Url> {
+        self.api.endpoint(endpoint)
     }
 
-    pub async fn schedule(&self, date: DateTime<Utc>) -> anyhow::Result<Schedule> {
-        let date = date.naive_utc().date();
-        self.client
-            .get(Url::from_str(&SCHEDULE_ENDPOINT)?)
-            .query(&[("student_id", self.student_id)])
-            .query(&[("date", date.to_string())])
-            .send()
-            .await?
-            .json()
+    async fn fetch<T: DeserializeOwned>(&self, request: HttpRequest) -> Result<T> {
+        self.api.fetch(request).await
+    }
+
+    pub async fn session(&self) -> Result<StudentSession> {
+        self.fetch(
+            HttpRequest::post(self.endpoint(Endpoint::Sessions)?).json(&StudentAuth {
+                auth_token: self.token().await?.token,
+            })?,
+        )
+        .await
+    }
+
+    pub async fn academic_years(&self) -> Result<Vec<AcademicYear>> {
+        self.fetch(HttpRequest::get(self.endpoint(Endpoint::AcademicYears)?))
             .await
-            .map_err(anyhow::Error::from)
     }
 
-    pub async fn final_marks(&self, year: &AcademicYear) -> anyhow::Result<Vec<FinalMark>> {
+    pub async fn schedule(&self, date: DateTime<Utc>) -> Result<Schedule> {
+        self.schedule_day(date.naive_utc().date()).await
+    }
+
+    async fn schedule_day(&self, date: NaiveDate) -> Result<Schedule> {
+        self.fetch(
+            HttpRequest::get(self.endpoint(Endpoint::Schedule)?)
+                .query(&[("student_id", self.student_id)])
+                .query(&[("date", date.to_string())]),
+        )
+        .await
+    }
+
+    /// Schedules of all days between `from` and `to` (both inclusive), fetched concurrently.
+    /// Days are yielded in order, each with its own result
+    pub fn schedule_stream(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        options: RangeOptions,
+    ) -> impl Stream<Item = (NaiveDate, Result<Schedule>)> + '_ {
+        let dates: Vec<NaiveDate> = from
+            .date_naive()
+            .iter_days()
+            .take_while(|date| *date <= to.date_naive())
+            .filter(|date| !options.skips(date))
+            .collect();
+        stream::iter(dates)
+            .map(move |date| async move { (date, self.schedule_day(date).await) })
+            .buffered(options.concurrency.max(1))
+    }
+
+    /// Same as [schedule_stream](Diary::schedule_stream), but collects all days
+    pub async fn schedule_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        options: RangeOptions,
+    ) -> Vec<(NaiveDate, Result<Schedule>)> {
+        self.schedule_stream(from, to, options).collect().await
+    }
+
+    pub async fn final_marks(&self, year: &AcademicYear) -> Result<Vec<FinalMark>> {
         self.final_marks_id(year.id).await
     }
 
-    pub async fn final_marks_id(&self, year_id: u16) -> anyhow::Result<Vec<FinalMark>> {
-        self.client
-            .get(Url::from_str(&FINAL_MARKS_PREV_YEAR_ENDPOINT)?)
-            .query(&[("student_profile_id", self.student_id)])
-            .query(&[("academic_year_id", year_id)])
-            .query(&[("is_year_mark", true)])
-            .header("Profile-Type", "student")
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(anyhow::Error::from)
+    pub async fn final_marks_id(&self, year_id: u16) -> Result<Vec<FinalMark>> {
+        self.fetch(
+            HttpRequest::get(self.endpoint(Endpoint::FinalMarksPrevYear)?)
+                .query(&[("student_profile_id", self.student_id)])
+                .query(&[("academic_year_id", year_id)])
+                .query(&[("is_year_mark", true)])
+                .header("Profile-Type", ProfileType::Student.as_str()),
+        )
+        .await
     }
 
-    async fn lesson_schedule_item(&self, lesson_id: u64) -> anyhow::Result<LessonScheduleItem> {
-        self.client
-            .get(Url::from_str(&format!(
-                "https://dnevnik.mos.ru/mobile/api/lesson_schedule_items/{}",
+    async fn lesson_schedule_item(&self, lesson_id: u64) -> Result<LessonScheduleItem> {
+        self.fetch(
+            HttpRequest::get(parse_url(&format!(
+                "{}/{}",
+                self.api.config.endpoint_url(Endpoint::LessonScheduleItems),
                 lesson_id
             ))?)
             .query(&[("student_id", self.student_id)])
-            .query(&[("type", "OO")])
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(anyhow::Error::from)
+            .query(&[("type", "OO")]),
+        )
+        .await
     }
 
     /// Gets module lesson plan for the provided lesson.
     /// Returns `Err` when the lesson lacks a scheduled plan (at least according to API)
-    pub async fn lesson_plan(&self, lesson: &LessonInstance) -> anyhow::Result<LessonPlan> {
-        let schedule_item = self.lesson_schedule_item(lesson.schedule_id).await?;
-        if schedule_item.plan_id.is_none() {
-            bail!(
-                "Could not get plan ID for the lesson {}!",
-                lesson.subject_name
-            )
-        }
-        self.lesson_plan_wid(schedule_item.plan_id.unwrap()).await
+    pub async fn lesson_plan(&self, lesson: &LessonInstance) -> Result<LessonPlan> {
+        self.lesson_plan_for(lesson.schedule_id).await
+    }
+
+    /// Gets module lesson plan for the lesson with the provided schedule item ID
+    pub async fn lesson_plan_for(&self, schedule_id: u64) -> Result<LessonPlan> {
+        let schedule_item = self.lesson_schedule_item(schedule_id).await?;
+        let plan_id = schedule_item.plan_id.ok_or_else(|| {
+            DnevnikError::missing("plan_id", format!("schedule item {}", schedule_id))
+        })?;
+        self.lesson_plan_wid(plan_id).await
     }
 
     /// Gets module lesson plan with the provided lesson plan ID
-    pub async fn lesson_plan_wid(&self, plan_id: u64) -> anyhow::Result<LessonPlan> {
+    pub async fn lesson_plan_wid(&self, plan_id: u64) -> Result<LessonPlan> {
         let ele: Vec<LessonPlan> = self
-            .client
-            .get(Url::from_str(&LESSON_PLANS_ENDPOINT)?)
-            .query(&[("plan_id", plan_id)])
-            .query(&[("ignore_owner", true)])
-            .query(&[("with_modules", true)])
-            .query(&[("with_topics", true)])
-            .query(&[("status", "for_calendar_plan")])
-            .header(ACCEPT, "application/json")
-            .send()
-            .await?
-            .json()
+            .fetch(
+                HttpRequest::get(self.endpoint(Endpoint::LessonPlans)?)
+                    .query(&[("plan_id", plan_id)])
+                    .query(&[("ignore_owner", true)])
+                    .query(&[("with_modules", true)])
+                    .query(&[("with_topics", true)])
+                    .query(&[("status", "for_calendar_plan")])
+                    .header(ACCEPT, "application/json"),
+            )
             .await?;
-        Ok(ele[0].to_owned())
+        ele.into_iter()
+            .next()
+            .ok_or_else(|| DnevnikError::missing("lesson_plan", format!("plan {}", plan_id)))
     }
 
     pub async fn homework(
         &self,
         from: DateTime<Utc>,
         to: DateTime<Utc>,
-    ) -> anyhow::Result<Vec<StudentHomework>> {
-        self.client
-            .get(Url::from_str(&STUDENT_HOMEWORKS_ENDPOINT)?)
-            .query(&[("begin_prepared_date", from.format("%d.%m.%Y").to_string())])
-            .query(&[("end_prepared_date", to.format("%d.%m.%Y").to_string())])
-            .query(&[("student_profile_id", self.student_id)])
-            .send()
-            .await?
-            .json()
-            .await
-            .map_err(anyhow::Error::from)
+    ) -> Result<Vec<StudentHomework>> {
+        self.fetch(
+            HttpRequest::get(self.endpoint(Endpoint::StudentHomeworks)?)
+                .query(&[("begin_prepared_date", from.format("%d.%m.%Y").to_string())])
+                .query(&[("end_prepared_date", to.format("%d.%m.%Y").to_string())])
+                .query(&[("student_profile_id", self.student_id)]),
+        )
+        .await
     }
 
     pub async fn download_attachment(
         &self,
         path: PathBuf,
         attachment: &HomeworkAttachment,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         let bytes = self
-            .client
-            .get(Url::from_str(&format!(
-                "{}{}",
-                GLOBAL_DMR_URL, attachment.relative_path
-            ))?)
-            .timeout(Duration::from_secs(15))
-            .send()
-            .await?
-            .bytes()
+            .api
+            .download(
+                HttpRequest::get(parse_url(
+                    &self.api.config.resolve(&attachment.relative_path),
+                )?)
+                .timeout(self.api.config.download_timeout),
+            )
             .await?;
         let mut file = tokio::fs::File::create(path).await?;
         let mut content = Cursor::new(bytes);
         let size_copied = tokio::io::copy(&mut content, &mut file).await?;
         if size_copied < attachment.file_size {
-            bail!("Could not download file, size of file downloaded is less than size of file provided by the attachments ({} < {})", size_copied, attachment.file_size)
+            return Err(DnevnikError::IncompleteDownload {
+                file_name: attachment.file_name.clone(),
+                expected: attachment.file_size,
+                received: size_copied,
+            });
         }
 
         Ok(())
     }
 
     /// Gets the progress report for the current student
-    pub async fn progress(&self) -> anyhow::Result<Vec<GlobalAverageGrade>> {
+    pub async fn progress(&self) -> Result<Vec<GlobalAverageGrade>> {
         // get current year
-        let year = self.academic_years().await?.last().unwrap().id;
-        self.client
-            .get(Url::from_str(&PROGRESS_ENDPOINT)?)
-            .query(&[("academic_year_id", year)])
-            .query(&[("student_profile_id", self.student_id)])
-            .send()
+        let year = self
+            .academic_years()
             .await?
-            .json()
-            .await
-            .map_err(anyhow::Error::from)
+            .last()
+            .ok_or_else(|| DnevnikError::missing("academic_year", "academic years list"))?
+            .id;
+        self.fetch(
+            HttpRequest::get(self.endpoint(Endpoint::Progress)?)
+                .query(&[("academic_year_id", year)])
+                .query(&[("student_profile_id", self.student_id)]),
+        )
+        .await
     }
 
     pub async fn visits(
         &self,
         from: DateTime<Utc>,
         to: DateTime<Utc>,
-    ) -> anyhow::Result<Vec<StudentAttendance>> {
+    ) -> Result<Vec<StudentAttendance>> {
+        let contract_id = self
+            .child()
+            .and_then(|child| child.contract_id)
+            .ok_or_else(|| DnevnikError::missing("contract_id", "student profile"))?;
         let data: Payload<Vec<StudentAttendance>> = self
-            .client
-            .get(Url::from_str(&VISITS_ENDPOINT)?)
-            .query(&[("from", from.date().to_string())])
-            .query(&[("to", to.date().to_string())])
-            .query(&[(
-                "contract_id",
-                self.profile.details().contract_id.ok_or_else(|| {
-                    anyhow::Error::msg("Provided student profile did not have `contract_id`!")
-                })?,
-            )])
-            .send()
-            .await?
-            .json()
+            .fetch(
+                HttpRequest::get(self.endpoint(Endpoint::Visits)?)
+                    .query(&[("from", from.date_naive().to_string())])
+                    .query(&[("to", to.date_naive().to_string())])
+                    .query(&[("contract_id", contract_id)]),
+            )
             .await?;
         Ok(data.payload)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{diary_with, fixture};
+    use crate::transport::MemoryTransport;
+    use chrono::TimeZone;
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_endpoint_statics() {
+        assert_eq!(
+            *PROFILE_ENDPOINT,
+            "https://dnevnik.mos.ru/mobile/api/profile"
+        );
+        assert_eq!(
+            *PROGRESS_ENDPOINT,
+            "https://dnevnik.mos.ru/jersey/api/progress/json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_representative_children() -> anyhow::Result<()> {
+        let mut profile = fixture("get_mobile_api_profile.json");
+        profile["profile"]["id"] = 1000002.into();
+        profile["profile"]["type"] = serde_json::Value::Null;
+        let mut second = profile["children"][0].clone();
+        second["id"] = 1000003.into();
+        second["contract_id"] = 2000003.into();
+        profile["children"].as_array_mut().unwrap().push(second);
+        let transport = Arc::new(
+            MemoryTransport::new()
+                .json("/mobile/api/profile", &profile)
+                .json("/mobile/api/visits", &serde_json::json!({ "payload": [] })),
+        );
+        let diary = diary_with(transport.clone()).build().await?;
+        assert!(diary.profile.is_representative());
+        assert_eq!(diary.children().len(), 2);
+        assert_eq!(diary.student_id(), 1000001);
+
+        let second = diary.for_child(1000003)?;
+        assert_eq!(second.student_id(), 1000003);
+        let date = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        second.visits(date, date).await?;
+        let query = transport
+            .requests()
+            .last()
+            .unwrap()
+            .url
+            .query()
+            .unwrap()
+            .to_string();
+        assert!(query.contains("contract_id=2000003"));
+        assert!(diary.for_child(42).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,436 @@
+//! Change detection between two snapshots of the diary
+//!
+//! Lessons are compared only on days present in both snapshots, so moving the fetched
+//! period forward does not report the marks of newly covered days as new.
+
+use crate::diary::{Diary, RangeOptions};
+use crate::error::Result;
+use crate::model::attendance::{StudentAttendance, StudentVisit};
+use crate::model::hw::StudentHomework;
+use crate::model::lessons::{LessonActivity, MarkInstance, Schedule, ScheduleActivity};
+use crate::model::marks::GlobalAverageGrade;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Schedules and homework of a student at some point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiarySnapshot {
+    /// Time at which this snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    pub schedules: Vec<Schedule>,
+    pub homeworks: Vec<StudentHomework>,
+    /// First and last due dates the homework was fetched for, `None` if unknown
+    pub homework_period: Option<(NaiveDate, NaiveDate)>,
+}
+
+impl DiarySnapshot {
+    pub fn new(schedules: Vec<Schedule>, homeworks: Vec<StudentHomework>) -> Self {
+        Self {
+            taken_at: Utc::now(),
+            schedules,
+            homeworks,
+            homework_period: None,
+        }
+    }
+
+    /// Sets the due dates the homework was fetched for
+    pub fn homework_period(mut self, from: NaiveDate, to: NaiveDate) -> Self {
+        self.homework_period = Some((from, to));
+        self
+    }
+
+    /// Takes a snapshot of the diary for the provided period
+    pub async fn fetch(diary: &Diary, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Self> {
+        let (schedules, _) = Self::fetch_schedules(diary, from, to).await?;
+        let homeworks = diary.homework(from, to).await?;
+        Ok(Self::new(schedules, homeworks).homework_period(from.date_naive(), to.date_naive()))
+    }
+
+    /// Schedules of the period along with the days that could not be fetched.
+    /// Fails only if none of the days could be fetched
+    pub(crate) async fn fetch_schedules(
+        diary: &Diary,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<(Vec<Schedule>, Vec<NaiveDate>)> {
+        let mut schedules = vec![];
+        let mut failed = vec![];
+        let mut error = None;
+        for (date, schedule) in diary
+            .schedule_range(from, to, RangeOptions::default())
+            .await
+        {
+            match schedule {
+                Ok(schedule) => schedules.push(schedule),
+                Err(e) => {
+                    log::warn!("Could not fetch schedule of {}: {}", date, e);
+                    failed.push(date);
+                    error = Some(e);
+                }
+            }
+        }
+        match error {
+            Some(error) if schedules.is_empty() => Err(error),
+            _ => Ok((schedules, failed)),
+        }
+    }
+
+    fn lessons(&self) -> HashMap<u64, (NaiveDate, &LessonActivity)> {
+        self.schedules
+            .iter()
+            .flat_map(|schedule| {
+                schedule
+                    .lessons
+                    .iter()
+                    .filter_map(|activity| match activity {
+                        ScheduleActivity::Lesson(lesson) => {
+                            Some((lesson.subject.schedule_id, (schedule.date, lesson.as_ref())))
+                        }
+                        ScheduleActivity::Break(_) => None,
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Short description of the lesson an event happened on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LessonRef {
+    /// Unique ID of the scheduled lesson
+    pub schedule_id: u64,
+    pub date: NaiveDate,
+    pub subject_name: String,
+    /// UTC time when the lesson begins
+    pub begin: DateTime<Utc>,
+}
+
+impl LessonRef {
+    fn new(date: NaiveDate, lesson: &LessonActivity) -> Self {
+        Self {
+            schedule_id: lesson.subject.schedule_id,
+            date,
+            subject_name: lesson.subject.subject_name.clone(),
+            begin: lesson.begin,
+        }
+    }
+}
+
+/// Change between two snapshots of the diary
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DiaryEvent {
+    NewMark {
+        lesson: LessonRef,
+        mark: MarkInstance,
+    },
+    /// The mark was updated, according to its `updated_at`
+    MarkChanged {
+        lesson: LessonRef,
+        old: MarkInstance,
+        new: MarkInstance,
+    },
+    MarkDeleted {
+        lesson: LessonRef,
+        mark: MarkInstance,
+    },
+    HomeworkAdded {
+        homework: StudentHomework,
+    },
+    /// The homework entry was updated, according to its `updated_at`
+    HomeworkUpdated {
+        old: StudentHomework,
+        new: StudentHomework,
+    },
+    /// The homework entry got its `deleted_at` set, or disappeared from the period
+    /// covered by both snapshots
+    HomeworkDeleted {
+        homework: StudentHomework,
+    },
+    LessonCancelled {
+        lesson: LessonRef,
+    },
+    RoomChanged {
+        lesson: LessonRef,
+        old_room: String,
+        new_room: String,
+    },
+    /// The student was marked as missing the lesson
+    AbsenceRecorded {
+        lesson: LessonRef,
+    },
+    /// Five-based average grade of the subject changed
+    AverageChanged {
+        subject_name: String,
+        old: Option<f32>,
+        new: Option<f32>,
+    },
+    /// The student entered the school building or the exit time of a visit became known
+    VisitRecorded {
+        date: NaiveDate,
+        visit: StudentVisit,
+    },
+}
+
+impl DiaryEvent {
+    /// Name of the event type, as in its JSON representation
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DiaryEvent::NewMark { .. } => "new_mark",
+            DiaryEvent::MarkChanged { .. } => "mark_changed",
+            DiaryEvent::MarkDeleted { .. } => "mark_deleted",
+            DiaryEvent::HomeworkAdded { .. } => "homework_added",
+            DiaryEvent::HomeworkUpdated { .. } => "homework_updated",
+            DiaryEvent::HomeworkDeleted { .. } => "homework_deleted",
+            DiaryEvent::LessonCancelled { .. } => "lesson_cancelled",
+            DiaryEvent::RoomChanged { .. } => "room_changed",
+            DiaryEvent::AbsenceRecorded { .. } => "absence_recorded",
+            DiaryEvent::AverageChanged { .. } => "average_changed",
+            DiaryEvent::VisitRecorded { .. } => "visit_recorded",
+        }
+    }
+}
+
+/// Events describing the changes from the `old` snapshot to the `new` one
+pub fn diff(old: &DiarySnapshot, new: &DiarySnapshot) -> Vec<DiaryEvent> {
+    let mut events = vec![];
+    diff_lessons(old, new, &mut events);
+    diff_homeworks(old, new, &mut events);
+    events
+}
+
+/// Events describing the changes of the average grades between two progress reports
+pub fn diff_progress(old: &[GlobalAverageGrade], new: &[GlobalAverageGrade]) -> Vec<DiaryEvent> {
+    new.iter()
+        .filter_map(|grade| {
+            let before = old
+                .iter()
+                .find(|old| old.subject_name == grade.subject_name)?;
+            (before.five != grade.five).then(|| DiaryEvent::AverageChanged {
+                subject_name: grade.subject_name.clone(),
+                old: before.five,
+                new: grade.five,
+            })
+        })
+        .collect()
+}
+
+/// Events describing new or completed visits between two attendance reports
+pub fn diff_visits(old: &[StudentAttendance], new: &[StudentAttendance]) -> Vec<DiaryEvent> {
+    let mut events = vec![];
+    for attendance in new {
+        let before = old.iter().find(|old| old.date == attendance.date);
+        for visit in &attendance.visits {
+            let seen = before
+                .map(|before| {
+                    before
+                        .visits
+                        .iter()
+                        .any(|old| old.entrance == visit.entrance && old.exit == visit.exit)
+                })
+                .unwrap_or(false);
+            if !seen {
+                events.push(DiaryEvent::VisitRecorded {
+                    date: attendance.date,
+                    visit: visit.clone(),
+                });
+            }
+        }
+    }
+    events
+}
+
+fn diff_lessons(old: &DiarySnapshot, new: &DiarySnapshot, events: &mut Vec<DiaryEvent>) {
+    let old_lessons = old.lessons();
+    let mut new_lessons: Vec<_> = new.lessons().into_iter().collect();
+    new_lessons.sort_by_key(|(_, (_, lesson))| lesson.begin);
+    for (id, (date, lesson)) in new_lessons {
+        let before = match old_lessons.get(&id) {
+            Some((_, before)) => before,
+            None => continue,
+        };
+        let lesson_ref = || LessonRef::new(date, lesson);
+        if lesson.subject.is_cancelled && !before.subject.is_cancelled {
+            events.push(DiaryEvent::LessonCancelled {
+                lesson: lesson_ref(),
+            });
+        }
+        if lesson.room != before.room {
+            events.push(DiaryEvent::RoomChanged {
+                lesson: lesson_ref(),
+                old_room: before.room.clone(),
+                new_room: lesson.room.clone(),
+            });
+        }
+        if lesson.subject.is_missed_lesson && !before.subject.is_missed_lesson {
+            events.push(DiaryEvent::AbsenceRecorded {
+                lesson: lesson_ref(),
+            });
+        }
+        for mark in &lesson.subject.marks {
+            match before.subject.marks.iter().find(|old| old.id == mark.id) {
+                None => events.push(DiaryEvent::NewMark {
+                    lesson: lesson_ref(),
+                    mark: mark.clone(),
+                }),
+                Some(old) if old.updated_at != mark.updated_at => {
+                    events.push(DiaryEvent::MarkChanged {
+                        lesson: lesson_ref(),
+                        old: old.clone(),
+                        new: mark.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for mark in &before.subject.marks {
+            if !lesson.subject.marks.iter().any(|new| new.id == mark.id) {
+                events.push(DiaryEvent::MarkDeleted {
+                    lesson: lesson_ref(),
+                    mark: mark.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Homework is compared only if it is due in the period covered by both snapshots, so
+/// moving the period forward does not report the homework of newly covered days as added
+fn diff_homeworks(old: &DiarySnapshot, new: &DiarySnapshot, events: &mut Vec<DiaryEvent>) {
+    let overlap = match (old.homework_period, new.homework_period) {
+        (Some((old_from, old_to)), Some((new_from, new_to))) => {
+            Some((old_from.max(new_from), old_to.min(new_to)))
+        }
+        _ => None,
+    };
+    let covered = |homework: &&StudentHomework| {
+        let due_date = homework.homework_entry.due_date();
+        match (overlap, due_date) {
+            (Some((from, to)), Some(due_date)) => from <= due_date && due_date <= to,
+            _ => true,
+        }
+    };
+    let old_homeworks: HashMap<u64, &StudentHomework> =
+        old.homeworks.iter().map(|hw| (hw.id, hw)).collect();
+    for homework in new.homeworks.iter().filter(covered) {
+        let entry = &homework.homework_entry;
+        match old_homeworks.get(&homework.id) {
+            None if entry.deleted_at.is_none() => events.push(DiaryEvent::HomeworkAdded {
+                homework: homework.clone(),
+            }),
+            None => {}
+            Some(before)
+                if entry.deleted_at.is_some() && before.homework_entry.deleted_at.is_none() =>
+            {
+                events.push(DiaryEvent::HomeworkDeleted {
+                    homework: homework.clone(),
+                })
+            }
+            Some(_) if entry.deleted_at.is_some() => {}
+            Some(before) if before.homework_entry.updated_at != entry.updated_at => {
+                events.push(DiaryEvent::HomeworkUpdated {
+                    old: (*before).clone(),
+                    new: homework.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    let new_ids: HashSet<u64> = new.homeworks.iter().map(|hw| hw.id).collect();
+    for homework in old.homeworks.iter().filter(covered) {
+        if !new_ids.contains(&homework.id) && homework.homework_entry.deleted_at.is_none() {
+            events.push(DiaryEvent::HomeworkDeleted {
+                homework: homework.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+
+    fn snapshot() -> DiarySnapshot {
+        let schedule = fixture("get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json");
+        let homeworks = fixture("get_core_api_student_homeworks@begin_prepared_date=10.10.2022&end_prepared_date=24.10.2022&student_profile_id=1000001.json");
+        DiarySnapshot::new(
+            vec![serde_json::from_value(schedule).unwrap()],
+            serde_json::from_value(homeworks).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_diff_homework_in_overlap() {
+        let day = |day| NaiveDate::from_ymd_opt(2022, 10, day).unwrap();
+        let mut old = snapshot().homework_period(day(10), day(12));
+        old.homeworks
+            .retain(|homework| homework.homework_entry.due_date() <= Some(day(12)));
+        let mut new = snapshot().homework_period(day(11), day(17));
+        // physics and history are due after the old period, so they are not new
+        assert!(diff(&old, &new).is_empty());
+
+        let mut added = new.homeworks[0].clone();
+        added.id = 9300;
+        new.homeworks.push(added);
+        let events = diff(&old, &new);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), "homework_added");
+
+        // homework due in both periods that is gone from the new one was deleted
+        new.homeworks
+            .retain(|homework| homework.homework_entry.due_date() != Some(day(12)));
+        let events: Vec<&str> = diff(&old, &new).iter().map(DiaryEvent::kind).collect();
+        assert!(events.contains(&"homework_deleted"));
+    }
+
+    fn lesson(snapshot: &mut DiarySnapshot, index: usize) -> &mut LessonActivity {
+        let lessons =
+            snapshot.schedules[0]
+                .lessons
+                .iter_mut()
+                .filter_map(|activity| match activity {
+                    ScheduleActivity::Lesson(lesson) => Some(lesson.as_mut()),
+                    ScheduleActivity::Break(_) => None,
+                });
+        lessons.into_iter().nth(index).unwrap()
+    }
+
+    #[test]
+    fn test_diff_events() {
+        let old = snapshot();
+        assert!(diff(&old, &old).is_empty());
+
+        let mut new = snapshot();
+        let algebra = lesson(&mut new, 0);
+        let mut mark = algebra.subject.marks[0].clone();
+        algebra.subject.marks[0].updated_at += chrono::Duration::minutes(5);
+        mark.id = 9100;
+        algebra.subject.marks.push(mark);
+        algebra.room = "999".to_string();
+        lesson(&mut new, 1).subject.is_missed_lesson = true;
+        lesson(&mut new, 2).subject.marks.clear();
+        let deleted = &mut new.homeworks[1].homework_entry;
+        deleted.deleted_at = Some(deleted.updated_at);
+        new.homeworks.remove(0);
+
+        let events = diff(&old, &new);
+        for event in &events {
+            assert_eq!(serde_json::to_value(event).unwrap()["type"], event.kind());
+        }
+        let events: Vec<&str> = events.iter().map(DiaryEvent::kind).collect();
+        assert_eq!(
+            events,
+            [
+                "room_changed",
+                "mark_changed",
+                "new_mark",
+                "absence_recorded",
+                "mark_deleted",
+                "homework_deleted",
+                // the first homework is missing from the new snapshot
+                "homework_deleted",
+            ]
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Error types returned by this crate
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Maximum amount of characters of a response body kept inside errors
+const SNIPPET_LENGTH: usize = 512;
+
+/// Result type with the [DnevnikError] as the error
+pub type Result<T> = std::result::Result<T, DnevnikError>;
+
+/// Errors that may happen when accessing the diary API
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum DnevnikError {
+    /// The server rejected the auth token (HTTP 401)
+    #[error("authentication failed for {endpoint} (HTTP {status})")]
+    Unauthorized {
+        endpoint: String,
+        status: StatusCode,
+    },
+    /// The login flow could not obtain a token
+    #[error("login failed: {reason}")]
+    Authentication { reason: String },
+    /// The server responded with a non-successful status code
+    #[error("{endpoint} responded with HTTP {status}: {body}")]
+    Status {
+        endpoint: String,
+        status: StatusCode,
+        /// Beginning of the response body
+        body: String,
+    },
+    /// The request did not complete in time
+    #[error("request to {endpoint} timed out")]
+    Timeout { endpoint: String },
+    /// The request could not be sent or the response could not be received
+    #[error("request to {endpoint} failed: {source}")]
+    Request {
+        endpoint: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    /// The response body did not match the expected schema
+    #[error("could not decode response of {endpoint}: {source} (body: {snippet})")]
+    Decode {
+        endpoint: String,
+        /// Beginning of the response body
+        snippet: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A value could not be serialized to JSON
+    #[error("could not encode {what}: {source}")]
+    Encode {
+        /// Description of the encoded value
+        what: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A value required to perform the request is missing
+    #[error("missing `{field}` in {context}")]
+    MissingField {
+        /// Name of the missing field
+        field: &'static str,
+        /// Description of where the field was expected
+        context: String,
+    },
+    /// The offline snapshot has no data for the request
+    #[error("no offline snapshot of {endpoint}")]
+    Offline { endpoint: String },
+    /// The account does not have the role required by the client
+    #[error("expected a {expected} account, got {}", .actual.as_deref().unwrap_or("representative"))]
+    WrongProfileType {
+        expected: &'static str,
+        /// Type of the account, `None` for representatives
+        actual: Option<String>,
+    },
+    /// Downloaded file is smaller than the size reported by the API
+    #[error("could not download {file_name}, downloaded size is less than the size provided by the attachment ({received} < {expected})")]
+    IncompleteDownload {
+        file_name: String,
+        expected: u64,
+        received: u64,
+    },
+    /// The configured URL is invalid
+    #[error("invalid URL `{url}`: {reason}")]
+    InvalidUrl { url: String, reason: String },
+    /// The token or another header value contains invalid characters
+    #[error("invalid header value: {0}")]
+    InvalidHeader(#[from] reqwest::header::InvalidHeaderValue),
+    /// The HTTP client could not be constructed
+    #[error("could not build HTTP client: {0}")]
+    Client(#[source] reqwest::Error),
+    /// The diary archive could not be read or written
+    #[cfg(feature = "storage")]
+    #[error("storage error: {0}")]
+    Storage(#[from] rusqlite::Error),
+    /// File system error
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl DnevnikError {
+    /// Whether this error means that the auth token is invalid or expired
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, DnevnikError::Unauthorized { .. })
+    }
+
+    /// HTTP status code of the response that caused this error, if any
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            DnevnikError::Unauthorized { status, .. } | DnevnikError::Status { status, .. } => {
+                Some(*status)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn missing(field: &'static str, context: impl Into<String>) -> Self {
+        DnevnikError::MissingField {
+            field,
+            context: context.into(),
+        }
+    }
+
+    pub(crate) fn from_status(endpoint: &str, status: StatusCode, body: &[u8]) -> Self {
+        if status == StatusCode::UNAUTHORIZED {
+            DnevnikError::Unauthorized {
+                endpoint: endpoint.to_string(),
+                status,
+            }
+        } else {
+            DnevnikError::Status {
+                endpoint: endpoint.to_string(),
+                status,
+                body: snippet(body),
+            }
+        }
+    }
+
+    pub(crate) fn from_request(endpoint: &str, error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            DnevnikError::Timeout {
+                endpoint: endpoint.to_string(),
+            }
+        } else {
+            DnevnikError::Request {
+                endpoint: endpoint.to_string(),
+                source: error,
+            }
+        }
+    }
+
+    pub(crate) fn encode(what: impl Into<String>, source: serde_json::Error) -> Self {
+        DnevnikError::Encode {
+            what: what.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn decode(endpoint: &str, body: &[u8], source: serde_json::Error) -> Self {
+        DnevnikError::Decode {
+            endpoint: endpoint.to_string(),
+            snippet: snippet(body),
+            source,
+        }
+    }
+}
+
+/// Lossy string containing at most [SNIPPET_LENGTH] characters of the body
+pub(crate) fn snippet(body: &[u8]) -> String {
+    String::from_utf8_lossy(body)
+        .chars()
+        .take(SNIPPET_LENGTH)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classification() {
+        let error = DnevnikError::from_status("/mobile/api/profile", StatusCode::UNAUTHORIZED, b"");
+        assert!(error.is_unauthorized());
+        let error = DnevnikError::from_status("/mobile/api/profile", StatusCode::FORBIDDEN, b"");
+        assert!(!error.is_unauthorized());
+        let error = DnevnikError::from_status(
+            "/mobile/api/profile",
+            StatusCode::BAD_GATEWAY,
+            "<html>".repeat(200).as_bytes(),
+        );
+        assert!(!error.is_unauthorized());
+        assert_eq!(error.status(), Some(StatusCode::BAD_GATEWAY));
+        match error {
+            DnevnikError::Status { body, .. } => assert_eq!(body.len(), SNIPPET_LENGTH),
+            _ => unreachable!(),
+        }
+    }
+}
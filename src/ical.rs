@@ -0,0 +1,198 @@
+//! Export of schedules to iCalendar (RFC 5545), for subscribing to the timetable in calendar apps
+//!
+//! Every lesson becomes a `VEVENT` whose UID is derived from its schedule item ID, so
+//! importing an updated export replaces the events instead of duplicating them.
+
+use crate::model::lessons::{LessonActivity, Schedule, ScheduleActivity};
+use crate::time::moscow;
+use chrono::{DateTime, Utc};
+
+/// Time zone of the events
+pub const TIMEZONE: &str = "Europe/Moscow";
+/// Lines longer than this many octets are folded
+const MAX_LINE: usize = 75;
+
+/// Builder of the iCalendar export
+#[derive(Debug, Clone)]
+pub struct CalendarExport {
+    name: String,
+    domain: String,
+    stamp: DateTime<Utc>,
+}
+
+impl Default for CalendarExport {
+    fn default() -> Self {
+        Self {
+            name: "Дневник".to_string(),
+            domain: "dnevnik.mos.ru".to_string(),
+            stamp: Utc::now(),
+        }
+    }
+}
+
+impl CalendarExport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name of the calendar shown by calendar apps
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Domain part of the event UIDs, the diary host by default
+    pub fn domain<S: Into<String>>(mut self, domain: S) -> Self {
+        self.domain = domain.into();
+        self
+    }
+
+    /// Time at which the export was created, the current time by default
+    pub fn stamp(mut self, stamp: DateTime<Utc>) -> Self {
+        self.stamp = stamp;
+        self
+    }
+
+    /// Renders the lessons of the schedules as an iCalendar file with CRLF line endings
+    pub fn render<'a, I: IntoIterator<Item = &'a Schedule>>(&self, schedules: I) -> String {
+        let mut calendar = Lines::default();
+        calendar.push("BEGIN:VCALENDAR");
+        calendar.push("VERSION:2.0");
+        calendar.push("PRODID:-//dnevnik//Schedule export//RU");
+        calendar.push("CALSCALE:GREGORIAN");
+        calendar.push("METHOD:PUBLISH");
+        calendar.property("X-WR-CALNAME", &self.name);
+        calendar.push(&format!("X-WR-TIMEZONE:{}", TIMEZONE));
+        calendar.push("BEGIN:VTIMEZONE");
+        calendar.push(&format!("TZID:{}", TIMEZONE));
+        calendar.push("BEGIN:STANDARD");
+        calendar.push("DTSTART:19700101T000000");
+        calendar.push("TZOFFSETFROM:+0300");
+        calendar.push("TZOFFSETTO:+0300");
+        calendar.push("TZNAME:MSK");
+        calendar.push("END:STANDARD");
+        calendar.push("END:VTIMEZONE");
+        for schedule in schedules {
+            for activity in &schedule.lessons {
+                if let ScheduleActivity::Lesson(lesson) = activity {
+                    self.event(&mut calendar, lesson);
+                }
+            }
+        }
+        calendar.push("END:VCALENDAR");
+        calendar.0
+    }
+
+    fn event(&self, calendar: &mut Lines, lesson: &LessonActivity) {
+        let subject = &lesson.subject;
+        calendar.push("BEGIN:VEVENT");
+        calendar.push(&format!("UID:{}@{}", subject.schedule_id, self.domain));
+        calendar.push(&format!("DTSTAMP:{}", self.stamp.format("%Y%m%dT%H%M%SZ")));
+        calendar.push(&format!(
+            "DTSTART;TZID={}:{}",
+            TIMEZONE,
+            local(lesson.begin)
+        ));
+        calendar.push(&format!("DTEND;TZID={}:{}", TIMEZONE, local(lesson.end)));
+        calendar.property("SUMMARY", &subject.subject_name);
+        let location: Vec<&str> = [lesson.room_name.as_str(), lesson.building.as_str()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect();
+        if !location.is_empty() {
+            calendar.property("LOCATION", &location.join(", "));
+        }
+        let mut description = format!("Учитель: {}", subject.teacher.name());
+        if !subject.homework.is_empty() {
+            description.push_str("\nДомашнее задание: ");
+            description.push_str(&subject.homework);
+        }
+        calendar.property("DESCRIPTION", &description);
+        calendar.push(if subject.is_cancelled {
+            "STATUS:CANCELLED"
+        } else {
+            "STATUS:CONFIRMED"
+        });
+        calendar.push("END:VEVENT");
+    }
+}
+
+fn local(time: DateTime<Utc>) -> String {
+    time.with_timezone(&moscow())
+        .format("%Y%m%dT%H%M%S")
+        .to_string()
+}
+
+/// Escapes the special characters of a text value
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Content lines of the calendar, folded to 75 octets without splitting characters
+#[derive(Debug, Default)]
+struct Lines(String);
+
+impl Lines {
+    fn push(&mut self, line: &str) {
+        let mut width = 0;
+        for c in line.chars() {
+            if width + c.len_utf8() > MAX_LINE {
+                self.0.push_str("\r\n ");
+                // the leading space counts towards the length of the continuation line
+                width = 1;
+            }
+            self.0.push(c);
+            width += c.len_utf8();
+        }
+        self.0.push_str("\r\n");
+    }
+
+    fn property(&mut self, name: &str, text: &str) {
+        self.push(&format!("{}:{}", name, escape(text)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_export_schedule() {
+        let mut schedule: Schedule = serde_json::from_value(fixture(
+            "get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json",
+        ))
+        .unwrap();
+        if let ScheduleActivity::Lesson(lesson) = &mut schedule.lessons[0] {
+            lesson.subject.homework = "№ 215, 216; повторить формулы сокращённого умножения и \
+                                       решить задачи из раздела для самостоятельной работы"
+                .to_string();
+        }
+        let ics = CalendarExport::new()
+            .stamp(Utc.with_ymd_and_hms(2022, 10, 1, 0, 0, 0).unwrap())
+            .render([&schedule]);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n") && ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 4);
+        assert_eq!(ics.matches("STATUS:CANCELLED").count(), 1);
+        assert!(ics.contains("UID:5001@dnevnik.mos.ru\r\n"));
+        assert!(ics.contains("DTSTART;TZID=Europe/Moscow:20221012T083000\r\n"));
+        assert!(ics.contains("DTSTAMP:20221001T000000Z\r\n"));
+        assert!(ics.contains("№ 215\\, 216\\;"));
+        for line in ics.split("\r\n") {
+            assert!(line.len() <= MAX_LINE, "{:?} is not folded", line);
+        }
+    }
+}
@@ -1,170 +1,220 @@
+pub mod analytics;
+pub mod auth;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cache;
+pub mod cassette;
+mod client;
+pub mod config;
 pub mod diary;
+pub mod diff;
+pub mod error;
+pub mod ical;
 pub mod model;
+pub mod offline;
+pub mod planner;
 pub mod prelude;
+pub mod retry;
+#[cfg(feature = "storage")]
+pub mod storage;
+pub mod teacher;
+#[cfg(test)]
+mod testing;
+mod time;
+pub mod token;
+pub mod transport;
+pub mod watch;
+#[cfg(feature = "webhooks")]
+pub mod webhook;
 
 #[cfg(test)]
 mod tests {
-    use crate::diary::Diary;
+    use crate::cassette;
+    use crate::diary::{Diary, RangeOptions};
+    use crate::error::DnevnikError;
+    use crate::model::grade::MarkValue;
     use crate::model::lessons::{LessonActivity, ScheduleActivity};
-    use chrono::{Duration, Utc};
+    use crate::testing::TempDir;
+    use chrono::{DateTime, NaiveDate, TimeZone, Utc};
     use dotenv::dotenv;
     use std::env;
     use std::path::PathBuf;
 
+    /// Builds a diary replaying fixtures from `tests/fixtures`.
+    /// Set `DNEVNIK_RECORD=1` and `AUTH_TOKEN` to record them from the live API instead.
+    async fn diary() -> anyhow::Result<Diary> {
+        dotenv().ok();
+        let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let token = env::var("AUTH_TOKEN").unwrap_or_else(|_| "replay".to_string());
+        Ok(Diary::builder(token)
+            .transport(cassette::from_env(fixtures)?)
+            .build()
+            .await?)
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    fn lessons(activities: &[ScheduleActivity]) -> Vec<&LessonActivity> {
+        activities
+            .iter()
+            .filter_map(|ele| {
+                if let ScheduleActivity::Lesson(lesson) = ele {
+                    Some(&**lesson)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
     #[tokio::test]
     async fn test_basic_auth() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
-        println!(
-            "Student: {} {} {}",
-            diary.profile.account.first_name,
-            diary.profile.account.last_name,
-            diary.profile.account.middle_name
+        let diary = diary().await?;
+        assert_eq!(
+            diary.profile.account.profile_type.as_deref(),
+            Some("student")
         );
         let profile = &diary.profile;
-        println!("School: {}", profile.details().school.full_name);
+        assert_eq!(profile.details().school.short_name, "Школа № 1");
         let academic_years = diary.academic_years().await?;
         let current_year = academic_years
             .iter()
             .find(|year| year.is_current)
             .ok_or(anyhow::Error::msg("Could not find current academic year!"))?;
-        println!(
-            "Current Year: {}, Start/End ({}/{})",
-            current_year.description, current_year.begin_date, current_year.end_date
-        );
+        assert_eq!(current_year.description, "2022-2023");
         let finals = diary.final_marks(current_year).await?;
-        println!("{:#?}", finals);
+        assert_eq!(finals.len(), 2);
+        assert!(finals.iter().all(|mark| mark.attested));
         Ok(())
     }
 
     #[tokio::test]
     async fn test_schedule() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
-        let schedule = diary.schedule(Utc::now() - Duration::days(1)).await?;
-        println!("Schedule summary: {}", schedule.summary);
-        let with_marks = schedule
-            .lessons
-            .iter()
-            .filter_map(|lesson| {
-                if let ScheduleActivity::Lesson(lesson) = lesson {
-                    Some(lesson)
-                } else {
-                    None
-                }
-            })
-            .filter_map(|lesson| {
-                if !lesson.subject.marks.is_empty() {
-                    Some(&**lesson)
-                } else {
-                    None
-                }
-            })
+        let diary = diary().await?;
+        let schedule = diary.schedule(day(2022, 10, 12)).await?;
+        assert_eq!(schedule.date.to_string(), "2022-10-12");
+        assert_eq!(schedule.lessons.len(), 7);
+        let with_marks = lessons(&schedule.lessons)
+            .into_iter()
+            .filter(|lesson| !lesson.subject.marks.is_empty())
             .collect::<Vec<&LessonActivity>>();
-        for lesson in with_marks {
-            let mark = lesson.subject.marks.first().unwrap();
-            println!(
-                "Got mark with value `{}` and weight `{}` on {} for '{}'",
-                mark.value, mark.weight, lesson.subject.subject_name, mark.cause
-            )
-        }
+        assert_eq!(with_marks.len(), 2);
+        let mark = with_marks[0].subject.marks.first().unwrap();
+        assert_eq!(with_marks[0].subject.subject_name, "Алгебра");
+        assert_eq!(mark.value, MarkValue::Five(5.0));
+        assert_eq!(mark.weight, 2.0);
+        assert_eq!(mark.cause, "Контрольная работа");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schedule_range() -> anyhow::Result<()> {
+        let diary = diary().await?;
+        let options = RangeOptions::default()
+            .skip_weekends(true)
+            .holidays([NaiveDate::from_ymd_opt(2022, 10, 14).unwrap()]);
+        let days = diary
+            .schedule_range(day(2022, 10, 10), day(2022, 10, 16), options)
+            .await;
+        let dates: Vec<String> = days.iter().map(|(date, _)| date.to_string()).collect();
+        assert_eq!(
+            dates,
+            ["2022-10-10", "2022-10-11", "2022-10-12", "2022-10-13"]
+        );
+        // only the 12th is recorded, other days fail independently
+        assert!(days[2].1.is_ok());
+        assert_eq!(days.iter().filter(|(_, day)| day.is_err()).count(), 3);
         Ok(())
     }
 
     #[tokio::test]
     async fn test_final_marks() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
+        let diary = diary().await?;
         let marks = diary.final_marks_id(4).await?;
-        for mark in marks {
-            println!("{}: {}", mark.subject_name, mark.value)
-        }
+        assert_eq!(marks.len(), 3);
+        assert_eq!(marks[0].subject_name, "Математика");
+        assert_eq!(marks[0].value, 5.0);
         Ok(())
     }
 
     #[tokio::test]
     async fn test_lesson_plans() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
-        let schedule = diary.schedule(Utc::now() - Duration::days(1)).await?;
-        let lessons = schedule
-            .lessons
-            .iter()
-            .filter_map(|ele| {
-                if let ScheduleActivity::Lesson(lesson) = ele {
-                    Some(&**lesson)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<&LessonActivity>>();
-        for lesson in lessons {
-            if let Ok(plan) = diary.lesson_plan(&lesson.subject).await {
-                println!("{:#?}", plan);
+        let diary = diary().await?;
+        let schedule = diary.schedule(day(2022, 10, 12)).await?;
+        let lessons = lessons(&schedule.lessons);
+        let plan = diary.lesson_plan(&lessons[0].subject).await?;
+        assert_eq!(plan.name, "Алгебра 8 класс");
+        assert_eq!(plan.modules.len(), 2);
+        assert_eq!(plan.modules[0].topics.len(), 2);
+        let missing = diary.lesson_plan(&lessons[1].subject).await.unwrap_err();
+        assert!(matches!(
+            missing,
+            DnevnikError::MissingField {
+                field: "plan_id",
+                ..
             }
-        }
+        ));
         Ok(())
     }
 
     #[tokio::test]
     async fn test_sessions() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
+        let diary = diary().await?;
         let session = diary.session().await?;
-        println!("{:#?}", session);
+        assert_eq!(session.id, diary.profile.account.id);
         Ok(())
     }
 
     #[tokio::test]
     async fn test_homework_downloader() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
-        let homework = diary
-            .homework(
-                Utc::now() - Duration::days(2),
-                Utc::now() + Duration::weeks(2),
-            )
-            .await?;
-        for hw in homework {
-            if !hw.homework_entry.attachments.is_empty() {
-                let attachment = &hw.homework_entry.attachments[0];
-                println!(
-                    "Downloading attachment for {} ({})",
-                    hw.homework_entry.subject().name,
-                    attachment.file_name
-                );
-                let path = PathBuf::from(&attachment.file_name);
-                diary.download_attachment(path.clone(), attachment).await?;
-                tokio::fs::remove_file(path).await?;
-            }
-        }
+        let diary = diary().await?;
+        let homework = diary.homework(day(2022, 10, 10), day(2022, 10, 24)).await?;
+        assert_eq!(homework.len(), 4);
+        let with_attachments = homework
+            .iter()
+            .filter(|hw| !hw.homework_entry.attachments.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(with_attachments.len(), 1);
+        let hw = with_attachments[0];
+        assert_eq!(hw.homework_entry.subject().name, "Алгебра");
+        let attachment = &hw.homework_entry.attachments[0];
+        let dir = TempDir::new("attachments");
+        let path = dir.path().join(&attachment.file_name);
+        diary.download_attachment(path.clone(), attachment).await?;
+        assert_eq!(
+            tokio::fs::metadata(&path).await?.len(),
+            attachment.file_size
+        );
         Ok(())
     }
 
     #[tokio::test]
     async fn test_progress() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
+        let diary = diary().await?;
         let progress = diary.progress().await?;
-        for grade in progress {
-            println!(
-                "Current average grade for {}: {}",
-                grade.subject_name, grade.five
-            )
-        }
+        assert_eq!(progress.len(), 2);
+        assert_eq!(progress[0].subject_name, "Алгебра");
+        assert_eq!(progress[0].five, Some(4.75));
+        assert_eq!(progress[0].periods[0].marks.len(), 3);
         Ok(())
     }
 
     #[tokio::test]
     async fn test_visits() -> anyhow::Result<()> {
-        dotenv()?;
-        let diary = Diary::new(env::var("AUTH_TOKEN")?).await?;
-        let visits = diary
-            .visits(Utc::now() - Duration::days(7), Utc::now())
-            .await?;
-        for visit in visits {
-            println!("Visit: {:#?}", visit);
-        }
+        let diary = diary().await?;
+        let visits = diary.visits(day(2022, 10, 5), day(2022, 10, 12)).await?;
+        assert_eq!(visits.len(), 6);
+        assert!(visits.iter().all(|day| !day.visits.is_empty()));
+        assert_eq!(
+            visits
+                .iter()
+                .flat_map(|day| &day.visits)
+                .filter(|visit| visit.is_warning)
+                .count(),
+            1
+        );
         Ok(())
     }
 }
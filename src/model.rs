@@ -1,12 +1,31 @@
 pub mod attendance;
+pub mod grade;
 pub mod hw;
 pub mod lessons;
 pub mod marks;
+pub mod teacher;
 
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Role of an account, sent in the `Profile-Type` header of role-specific requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileType {
+    Student,
+    Teacher,
+}
+
+impl ProfileType {
+    /// Value of this type as used by the API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProfileType::Student => "student",
+            ProfileType::Teacher => "teacher",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StudentSession {
     /// Unique ID of this student
@@ -30,6 +49,9 @@ pub struct StudentSession {
     pub email: String,
     /// Individual insurance account number (SNILS) of the profile owner
     pub snils: String,
+    /// Auth token bound to this session, possibly renewed
+    #[serde(default)]
+    pub authentication_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -39,15 +61,39 @@ pub struct StudentProfile {
     /// Account bound to this student
     #[serde(rename = "profile")]
     pub account: Account,
-    /// A single element list containing details of this student
+    /// Details of the students bound to this account. A single element list for
+    /// student accounts, and all children for representative (parent) accounts
     #[serde(rename = "children")]
     details: Vec<StudentDetails>,
 }
 
 impl StudentProfile {
+    /// Details of the first student bound to this account
     pub fn details(&self) -> &StudentDetails {
         &self.details[0]
     }
+
+    /// Details of all students bound to this account
+    pub fn children(&self) -> &[StudentDetails] {
+        &self.details
+    }
+
+    /// Details of the student with the provided account ID
+    pub fn child(&self, id: u64) -> Option<&StudentDetails> {
+        self.details
+            .iter()
+            .find(|child| child.parent_account.id == id)
+    }
+
+    /// Whether this is a representative (parent) account
+    pub fn is_representative(&self) -> bool {
+        self.account.profile_type.is_none()
+    }
+
+    /// Whether this is a teacher account
+    pub fn is_teacher(&self) -> bool {
+        self.account.profile_type.as_deref() == Some(ProfileType::Teacher.as_str())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
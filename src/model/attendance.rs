@@ -1,4 +1,4 @@
-use chrono::NaiveDate;
+use chrono::{Duration, NaiveDate, NaiveTime};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -16,14 +16,15 @@ pub struct StudentAttendance {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct StudentVisit {
-    /// Time of entrance to the school
-    #[serde(rename = "in")]
-    pub entrance: String,
-    /// Time of exit out of the school
-    #[serde(rename = "out")]
-    pub exit: String,
-    /// Time spent inside school
-    pub duration: String,
+    /// Local time of entrance to the school
+    #[serde(rename = "in", with = "visit_serde::time")]
+    pub entrance: NaiveTime,
+    /// Local time of exit out of the school, `None` while the student is still inside
+    #[serde(rename = "out", with = "visit_serde::opt_time")]
+    pub exit: Option<NaiveTime>,
+    /// Time spent inside school, `None` while the student is still inside
+    #[serde(with = "visit_serde::duration")]
+    pub duration: Option<Duration>,
     /// Address of the building
     pub address: String,
     /// Unknown, all of the values I've witnessed were `COMMON`
@@ -34,3 +35,123 @@ pub struct StudentVisit {
     /// Short name of the school building
     pub short_name: String,
 }
+
+impl StudentVisit {
+    /// Time spent inside school, computed from the entrance and exit times if the server
+    /// didn't send it
+    pub fn time_spent(&self) -> Option<Duration> {
+        self.duration.or_else(|| Some(self.exit? - self.entrance))
+    }
+}
+
+/// Format of the visit times
+pub(crate) const TIME_FORMAT: &str = "%H:%M";
+
+/// Formats the duration as sent by the API, e.g. `5 ч. 45 мин.`
+pub fn format_duration(duration: Duration) -> String {
+    format!(
+        "{} ч. {} мин.",
+        duration.num_hours(),
+        duration.num_minutes() % 60
+    )
+}
+
+/// Parses a duration sent by the API, e.g. `5 ч. 45 мин.` or `45 мин.`
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut minutes = 0;
+    let mut parts = text.split_whitespace();
+    let mut parsed = false;
+    while let Some(number) = parts.next() {
+        let number: i64 = number.parse().ok()?;
+        minutes += match parts.next()?.trim_end_matches('.') {
+            "ч" => number * 60,
+            "мин" => number,
+            _ => return None,
+        };
+        parsed = true;
+    }
+    parsed.then(|| Duration::minutes(minutes))
+}
+
+#[doc(hidden)]
+mod visit_serde {
+    /// Exit times and durations are `-` or empty until the student leaves
+    fn is_missing(text: &str) -> bool {
+        let text = text.trim();
+        text.is_empty() || text == "-"
+    }
+
+    pub mod time {
+        use super::super::TIME_FORMAT;
+        use chrono::NaiveTime;
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(value: &NaiveTime, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_str(&value.format(TIME_FORMAT))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveTime, D::Error> {
+            let text = String::deserialize(d)?;
+            NaiveTime::parse_from_str(text.trim(), TIME_FORMAT)
+                .map_err(|e| de::Error::custom(format!("Parse error {} for {}", e, text)))
+        }
+    }
+
+    pub mod opt_time {
+        use super::super::TIME_FORMAT;
+        use chrono::NaiveTime;
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(value: &Option<NaiveTime>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match value {
+                Some(value) => s.collect_str(&value.format(TIME_FORMAT)),
+                None => s.serialize_str("-"),
+            }
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<NaiveTime>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let text = Option::<String>::deserialize(d)?.unwrap_or_default();
+            if super::is_missing(&text) {
+                return Ok(None);
+            }
+            NaiveTime::parse_from_str(text.trim(), TIME_FORMAT)
+                .map(Some)
+                .map_err(|e| de::Error::custom(format!("Parse error {} for {}", e, text)))
+        }
+    }
+
+    pub mod duration {
+        use super::super::{format_duration, parse_duration};
+        use chrono::Duration;
+        use serde::{de, Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S>(value: &Option<Duration>, s: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match value {
+                Some(value) => s.serialize_str(&format_duration(*value)),
+                None => s.serialize_str(""),
+            }
+        }
+
+        pub fn deserialize<'de, D>(d: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let text = Option::<String>::deserialize(d)?.unwrap_or_default();
+            if super::is_missing(&text) {
+                return Ok(None);
+            }
+            parse_duration(&text)
+                .map(Some)
+                .ok_or_else(|| de::Error::custom(format!("invalid duration {:?}", text)))
+        }
+    }
+}
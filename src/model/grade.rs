@@ -0,0 +1,347 @@
+//! Typed mark values and grading systems
+//!
+//! The API sends marks and grading systems as free-form strings. These types parse them and
+//! serialize to a normalized form, which can differ from the original string: "4,5" becomes
+//! "4.5", "Зачёт" becomes "зачёт", "осв." becomes "осв" and the number 8 becomes "8".
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+/// Grading system of a mark
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GradeSystem {
+    /// Marks from 2 to 5
+    Five,
+    /// Marks from 1 to 10
+    Ten,
+    /// Marks from 0 to 100
+    Hundred,
+    /// Pass or fail ("зачёт" or "незачёт")
+    PassFail,
+    /// System unknown to this crate, as named by the API
+    Other(String),
+}
+
+impl GradeSystem {
+    /// Value of this system as used by the API
+    pub fn as_str(&self) -> &str {
+        match self {
+            GradeSystem::Five => "five",
+            GradeSystem::Ten => "ten",
+            GradeSystem::Hundred => "hundred",
+            GradeSystem::PassFail => "zachet",
+            GradeSystem::Other(name) => name,
+        }
+    }
+
+    /// Highest mark of this system, `None` for non-numeric systems
+    pub fn max(&self) -> Option<f32> {
+        match self {
+            GradeSystem::Five => Some(5.0),
+            GradeSystem::Ten => Some(10.0),
+            GradeSystem::Hundred => Some(100.0),
+            GradeSystem::PassFail | GradeSystem::Other(_) => None,
+        }
+    }
+
+    /// Converts a numeric mark of this system to the `target` one proportionally to their
+    /// highest marks, e.g. a five-based 4 is a hundred-based 80
+    pub fn convert(&self, value: f32, target: &GradeSystem) -> Option<f32> {
+        Some(value / self.max()? * target.max()?)
+    }
+
+    /// Mark of this system with the provided numeric value. Non-finite and negative values
+    /// are not marks of any system and are kept as [MarkValue::Other]
+    pub fn mark(&self, value: f32) -> MarkValue {
+        if !is_mark(value) {
+            return MarkValue::Other(value.to_string());
+        }
+        match self {
+            GradeSystem::Five => MarkValue::Five(value),
+            GradeSystem::Ten => MarkValue::Ten(value),
+            GradeSystem::Hundred => MarkValue::Hundred(value),
+            GradeSystem::PassFail if value > 0.0 => MarkValue::Pass,
+            GradeSystem::PassFail => MarkValue::Fail,
+            GradeSystem::Other(_) => MarkValue::Other(value.to_string()),
+        }
+    }
+}
+
+/// Whether the number can be a numeric mark
+fn is_mark(value: f32) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+impl fmt::Display for GradeSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for GradeSystem {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "five" => GradeSystem::Five,
+            "ten" => GradeSystem::Ten,
+            "hundred" => GradeSystem::Hundred,
+            "zachet" => GradeSystem::PassFail,
+            other => GradeSystem::Other(other.to_string()),
+        })
+    }
+}
+
+impl Serialize for GradeSystem {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for GradeSystem {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(d)?;
+        Ok(name.parse().unwrap())
+    }
+}
+
+/// Value of a single mark
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkValue {
+    Five(f32),
+    Ten(f32),
+    Hundred(f32),
+    /// "зачёт"
+    Pass,
+    /// "незачёт"
+    Fail,
+    /// The student was absent, "н"
+    Absent,
+    /// The student is exempt from the subject, "осв"
+    Exempt,
+    /// Value unknown to this crate, as sent by the API
+    Other(String),
+}
+
+impl MarkValue {
+    /// Parses the value, reading numbers as marks of the provided system
+    pub fn parse_in(text: &str, system: &GradeSystem) -> Self {
+        match text.trim().replace(',', ".").parse::<f32>() {
+            Ok(value) if is_mark(value) && !matches!(system, GradeSystem::Other(_)) => {
+                system.mark(value)
+            }
+            _ => text.parse().unwrap(),
+        }
+    }
+
+    /// Grading system of this mark, `None` for the absence and exemption marks
+    pub fn system(&self) -> Option<GradeSystem> {
+        match self {
+            MarkValue::Five(_) => Some(GradeSystem::Five),
+            MarkValue::Ten(_) => Some(GradeSystem::Ten),
+            MarkValue::Hundred(_) => Some(GradeSystem::Hundred),
+            MarkValue::Pass | MarkValue::Fail => Some(GradeSystem::PassFail),
+            MarkValue::Absent | MarkValue::Exempt | MarkValue::Other(_) => None,
+        }
+    }
+
+    /// Numeric value of this mark in its own system
+    pub fn number(&self) -> Option<f32> {
+        match self {
+            MarkValue::Five(value) | MarkValue::Ten(value) | MarkValue::Hundred(value) => {
+                Some(*value)
+            }
+            _ => None,
+        }
+    }
+
+    /// The same mark in the `target` system, `None` for non-numeric marks
+    pub fn convert(&self, target: &GradeSystem) -> Option<MarkValue> {
+        let value = self.system()?.convert(self.number()?, target)?;
+        Some(target.mark(value))
+    }
+
+    /// Five-based value of this mark, `None` for non-numeric marks
+    pub fn five_based(&self) -> Option<f32> {
+        self.convert(&GradeSystem::Five)?.number()
+    }
+
+    /// Hundred-based value of this mark, `None` for non-numeric marks
+    pub fn hundred_based(&self) -> Option<f32> {
+        self.convert(&GradeSystem::Hundred)?.number()
+    }
+
+    /// Reads a numeric mark as a mark of the provided system, as the grading system of a
+    /// mark can't be told from its value alone
+    pub fn in_system(self, system: &GradeSystem) -> Self {
+        match self.number() {
+            Some(value) if !matches!(system, GradeSystem::Other(_)) => system.mark(value),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for MarkValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkValue::Five(value) | MarkValue::Ten(value) | MarkValue::Hundred(value) => {
+                write!(f, "{}", value)
+            }
+            MarkValue::Pass => f.write_str("зачёт"),
+            MarkValue::Fail => f.write_str("незачёт"),
+            MarkValue::Absent => f.write_str("н"),
+            MarkValue::Exempt => f.write_str("осв"),
+            MarkValue::Other(value) => f.write_str(value),
+        }
+    }
+}
+
+/// Numbers up to 5 are read as five-based marks, up to 10 as ten-based and larger ones
+/// as hundred-based. Non-finite and negative numbers are kept as [`MarkValue::Other`].
+/// Use [`MarkValue::in_system`] when the grading system is known
+impl FromStr for MarkValue {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim();
+        if let Ok(value) = text.replace(',', ".").parse::<f32>() {
+            if !is_mark(value) {
+                return Ok(MarkValue::Other(s.to_string()));
+            }
+            return Ok(match value {
+                value if value <= 5.0 => MarkValue::Five(value),
+                value if value <= 10.0 => MarkValue::Ten(value),
+                value => MarkValue::Hundred(value),
+            });
+        }
+        Ok(match text.to_lowercase().replace('ё', "е").as_str() {
+            "зачет" | "зач" => MarkValue::Pass,
+            "незачет" | "незач" => MarkValue::Fail,
+            "н" => MarkValue::Absent,
+            "осв" | "осв." | "освобожден" => MarkValue::Exempt,
+            _ => MarkValue::Other(s.to_string()),
+        })
+    }
+}
+
+impl Serialize for MarkValue {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for MarkValue {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_any(MarkValueVisitor)
+    }
+}
+
+struct MarkValueVisitor;
+
+impl<'de> de::Visitor<'de> for MarkValueVisitor {
+    type Value = MarkValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a mark string or number")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(value.parse().unwrap())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+        self.visit_str(&value.to_string())
+    }
+}
+
+/// (De)serialization of the average grades, sent as strings with two decimals.
+/// Empty and `"-"` averages mean that there are no marks yet
+pub(crate) mod average {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<f32>, s: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => s.collect_str(&format_args!("{:.2}", value)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<f32>, D::Error> {
+        let text = Option::<String>::deserialize(d)?.unwrap_or_default();
+        let text = text.trim().replace(',', ".");
+        if text.is_empty() || text == "-" {
+            return Ok(None);
+        }
+        text.parse()
+            .map(Some)
+            .map_err(|_| serde::de::Error::custom(format!("invalid average {:?}", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mark_values() {
+        let marks: Vec<MarkValue> =
+            serde_json::from_str(r#"["5", "4,5", 8, "85", "Зачёт", "н", "осв", "5-"]"#).unwrap();
+        assert_eq!(
+            marks,
+            [
+                MarkValue::Five(5.0),
+                MarkValue::Five(4.5),
+                MarkValue::Ten(8.0),
+                MarkValue::Hundred(85.0),
+                MarkValue::Pass,
+                MarkValue::Absent,
+                MarkValue::Exempt,
+                MarkValue::Other("5-".to_string()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&marks[..2]).unwrap(),
+            r#"["5","4.5"]"#
+        );
+
+        assert_eq!(MarkValue::Five(4.0).hundred_based(), Some(80.0));
+        assert_eq!(MarkValue::Ten(8.0).five_based(), Some(4.0));
+        assert_eq!(
+            MarkValue::Five(4.0).in_system(&GradeSystem::Hundred),
+            MarkValue::Hundred(4.0)
+        );
+        assert_eq!(
+            MarkValue::parse_in("0", &GradeSystem::PassFail),
+            MarkValue::Fail
+        );
+        assert_eq!(MarkValue::Absent.five_based(), None);
+        for text in ["NaN", "inf", "-3"] {
+            assert_eq!(
+                text.parse::<MarkValue>(),
+                Ok(MarkValue::Other(text.to_string()))
+            );
+            assert_eq!(
+                MarkValue::parse_in(text, &GradeSystem::Five),
+                MarkValue::Other(text.to_string())
+            );
+        }
+        assert_eq!(
+            GradeSystem::Ten.mark(f32::NAN),
+            MarkValue::Other("NaN".to_string())
+        );
+
+        let system: GradeSystem = serde_json::from_str(r#""letters""#).unwrap();
+        assert_eq!(system, GradeSystem::Other("letters".to_string()));
+        assert_eq!(serde_json::to_string(&system).unwrap(), r#""letters""#);
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -18,13 +18,22 @@ pub struct HomeworkEntry {
     /// ID of this homework entry
     pub id: u64,
     /// Date at which this homework entry was created
-    #[serde(deserialize_with = "datetime_de::deserialize_datetime")]
+    #[serde(
+        deserialize_with = "datetime_de::deserialize_datetime",
+        serialize_with = "datetime_de::serialize_datetime"
+    )]
     pub created_at: NaiveDateTime,
     /// Date at which this homework entry was last updated
-    #[serde(deserialize_with = "datetime_de::deserialize_datetime")]
+    #[serde(
+        deserialize_with = "datetime_de::deserialize_datetime",
+        serialize_with = "datetime_de::serialize_datetime"
+    )]
     pub updated_at: NaiveDateTime,
     /// Date at which this homework was deleted, `None` if it wasn't deleted
-    #[serde(deserialize_with = "datetime_de::deserialize_opt_datetime")]
+    #[serde(
+        deserialize_with = "datetime_de::deserialize_opt_datetime",
+        serialize_with = "datetime_de::serialize_opt_datetime"
+    )]
     pub deleted_at: Option<NaiveDateTime>,
     /// Text description for this homework entry
     pub description: String,
@@ -40,11 +49,33 @@ impl HomeworkEntry {
     pub fn subject(&self) -> &HomeworkSubject {
         &self.homework.subject
     }
+
+    /// Date of the lesson this homework should be prepared for
+    pub fn due_date(&self) -> Option<NaiveDate> {
+        self.homework.date_prepared_for
+    }
+
+    /// Date at which this homework was assigned
+    pub fn assigned_on(&self) -> Option<NaiveDate> {
+        self.homework.date_assigned_on
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct InternalHomeworkEntry {
     subject: HomeworkSubject,
+    #[serde(
+        default,
+        deserialize_with = "datetime_de::deserialize_opt_date",
+        serialize_with = "datetime_de::serialize_opt_date"
+    )]
+    date_assigned_on: Option<NaiveDate>,
+    #[serde(
+        default,
+        deserialize_with = "datetime_de::deserialize_opt_date",
+        serialize_with = "datetime_de::serialize_opt_date"
+    )]
+    date_prepared_for: Option<NaiveDate>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -60,7 +91,10 @@ pub struct HomeworkAttachment {
     /// ID of this attachment
     pub id: u64,
     /// Date at which this attachment was added
-    #[serde(deserialize_with = "datetime_de::deserialize_datetime")]
+    #[serde(
+        deserialize_with = "datetime_de::deserialize_datetime",
+        serialize_with = "datetime_de::serialize_datetime"
+    )]
     pub created_at: NaiveDateTime,
     /// Name of the attached file
     #[serde(rename = "file_file_name")]
@@ -94,8 +128,56 @@ mod datetime_de {
         d.deserialize_option(OptionalDateTimeFormatVisitor)
     }
 
-    use chrono::NaiveDateTime;
-    use serde::de;
+    /// Format of the dates used by the homework API
+    const FORMAT: &str = "%d.%m.%Y %H:%M";
+    /// Format of the days used by the homework API
+    const DATE_FORMAT: &str = "%d.%m.%Y";
+
+    #[doc(hidden)]
+    pub fn deserialize_opt_date<'de, D>(d: D) -> Result<Option<NaiveDate>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match Option::<String>::deserialize(d)? {
+            Some(value) => NaiveDate::parse_from_str(&value, DATE_FORMAT)
+                .map(Some)
+                .map_err(|e| de::Error::custom(format!("Parse error {} for {}", e, value))),
+            None => Ok(None),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn serialize_opt_date<S>(value: &Option<NaiveDate>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => s.collect_str(&value.format(DATE_FORMAT)),
+            None => s.serialize_none(),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn serialize_datetime<S>(value: &NaiveDateTime, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.collect_str(&value.format(FORMAT))
+    }
+
+    #[doc(hidden)]
+    pub fn serialize_opt_datetime<S>(value: &Option<NaiveDateTime>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serialize_datetime(value, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    use chrono::{NaiveDate, NaiveDateTime};
+    use serde::{de, Deserialize, Serializer};
     use std::fmt;
 
     struct OptionalDateTimeFormatVisitor;
@@ -135,7 +217,7 @@ mod datetime_de {
         where
             E: de::Error,
         {
-            match NaiveDateTime::parse_from_str(value, "%d.%m.%Y %H:%M") {
+            match NaiveDateTime::parse_from_str(value, FORMAT) {
                 Ok(ndt) => Ok(ndt),
                 Err(e) => Err(E::custom(format!("Parse error {} for {}", e, value))),
             }
@@ -1,3 +1,4 @@
+use crate::model::grade::{GradeSystem, MarkValue};
 use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -49,7 +50,7 @@ pub struct SystemBasedMarkValue {
     pub internal_grade_system_id: u64,
     /// Internal name for this grade system
     #[serde(rename = "grade_system_type")]
-    pub internal_grade_system_type: Option<String>,
+    pub internal_grade_system_type: Option<GradeSystem>,
     /// Graded value of this mark, contains the actual integer value in different systems
     pub grade: Grade,
 }
@@ -58,8 +59,8 @@ pub struct SystemBasedMarkValue {
 pub struct MarkInstance {
     /// Unique ID of this mark
     pub id: u64,
-    /// String value representation of this mark
-    pub value: String,
+    /// Value of this mark
+    pub value: MarkValue,
     /// Different grading system based values for this mark
     #[serde(rename = "values")]
     pub system_values: Vec<SystemBasedMarkValue>,
@@ -82,6 +83,29 @@ pub struct MarkInstance {
     pub is_point: bool,
 }
 
+impl MarkInstance {
+    /// Five- and hundred-based values of this mark in its own grading system
+    pub fn grade(&self) -> Option<&Grade> {
+        self.system_values.first().map(|value| &value.grade)
+    }
+
+    /// Grading system of this mark, if sent by the API
+    pub fn grade_system(&self) -> Option<&GradeSystem> {
+        self.system_values
+            .first()?
+            .internal_grade_system_type
+            .as_ref()
+    }
+
+    /// Value of this mark read in its own grading system, when it is known
+    pub fn typed_value(&self) -> MarkValue {
+        match self.grade_system() {
+            Some(system) => self.value.clone().in_system(system),
+            None => self.value.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LessonInstance {
     /// A unique ID for this scheduled item
@@ -195,7 +219,7 @@ pub struct FinalMark {
     pub value: f32,
     /// Type of the grading system
     #[serde(rename = "grade_system_type")]
-    pub grade_system: String,
+    pub grade_system: GradeSystem,
     /// Whether the student was attested this year
     pub attested: bool,
     /// Whether the student had academical debt this year
@@ -207,6 +231,13 @@ pub struct FinalMark {
     pub subject_name: String,
 }
 
+impl FinalMark {
+    /// Value of this mark in its grading system
+    pub fn mark(&self) -> MarkValue {
+        self.grade_system.mark(self.value)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LessonScheduleItem {
     /// ID of this schedule item
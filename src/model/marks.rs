@@ -1,3 +1,4 @@
+use crate::model::grade::{self, GradeSystem, MarkValue};
 use crate::model::lessons::MarkInstance;
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
@@ -6,12 +7,12 @@ use serde::{Deserialize, Serialize};
 pub struct GlobalAverageGrade {
     /// Name of the subject this grade belongs to
     pub subject_name: String,
-    /// Five-based average value for this grade
-    #[serde(rename = "avg_five")]
-    pub five: String,
-    /// Hundred-based average value for this grade
-    #[serde(rename = "avg_hundred")]
-    pub hundred: String,
+    /// Five-based average value for this grade, `None` without marks
+    #[serde(rename = "avg_five", with = "grade::average")]
+    pub five: Option<f32>,
+    /// Hundred-based average value for this grade, `None` without marks
+    #[serde(rename = "avg_hundred", with = "grade::average")]
+    pub hundred: Option<f32>,
     /// Average grades for the underlying periods
     pub periods: Vec<PeriodAverageGrade>,
 }
@@ -26,12 +27,12 @@ pub struct PeriodAverageGrade {
     /// Date at which this period ends
     #[serde(rename = "end_iso")]
     pub end: NaiveDate,
-    /// Five-based average value for this grade
-    #[serde(rename = "avg_five")]
-    pub five: String,
-    /// Hundred-based average value for this grade
-    #[serde(rename = "avg_hundred")]
-    pub hundred: String,
+    /// Five-based average value for this grade, `None` without marks
+    #[serde(rename = "avg_five", with = "grade::average")]
+    pub five: Option<f32>,
+    /// Hundred-based average value for this grade, `None` without marks
+    #[serde(rename = "avg_hundred", with = "grade::average")]
+    pub hundred: Option<f32>,
     /// All marks for this period
     pub marks: Vec<MarkInstance>,
 }
@@ -54,7 +55,7 @@ pub struct LocalGradeMark {
     pub control_form_id: u64,
     /// System of this grade
     #[serde(rename = "grade_system_type")]
-    pub grade_system: String,
+    pub grade_system: GradeSystem,
     /// Name of the topic this mark belongs to
     pub topic_name: String,
     /// Name of the control form that this mark belongs to
@@ -70,5 +71,5 @@ pub struct LocalGradeMarkValue {
     /// Hundred based value for this mark
     pub hundred: f32,
     /// Original value of this mark
-    pub original: String,
+    pub original: MarkValue,
 }
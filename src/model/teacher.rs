@@ -0,0 +1,174 @@
+use crate::model::grade::MarkValue;
+use crate::model::lessons::SystemBasedMarkValue;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TeacherScheduleItem {
+    /// Unique ID of this scheduled lesson
+    pub id: u64,
+    /// ID of the subject group attending this lesson
+    pub group_id: u64,
+    /// Name of the subject group attending this lesson
+    pub group_name: String,
+    /// ID of the subject of this lesson. `None` for section groups
+    pub subject_id: Option<u64>,
+    /// Name of the subject of this lesson
+    pub subject_name: String,
+    /// Local date and time when this lesson begins
+    #[serde(rename = "iso_date_time", deserialize_with = "date_time::deserialize")]
+    pub date_time: NaiveDateTime,
+    /// String representation of time when this lesson begins
+    pub begin_time: String,
+    /// String representation of time when this lesson ends
+    pub end_time: String,
+    /// Room number where this lesson takes place
+    pub room_number: Option<String>,
+    /// Whether this lesson was cancelled
+    #[serde(rename = "cancelled")]
+    pub is_cancelled: bool,
+}
+
+impl TeacherScheduleItem {
+    /// Date of this lesson
+    pub fn date(&self) -> NaiveDate {
+        self.date_time.date()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ClassStudent {
+    /// Unique profile ID of this student, used in other student related methods
+    pub id: u64,
+    /// Internal user ID of this student
+    pub user_id: Option<u64>,
+    /// Last name or surname of the student
+    pub last_name: String,
+    /// First name of the student
+    pub first_name: String,
+    /// Middle name or the patronymic of the student
+    pub middle_name: Option<String>,
+    /// Unique unit ID for the class this student belongs to
+    #[serde(rename = "class_unit_id")]
+    pub class_id: u64,
+    /// IDs of the subject groups this student belongs to
+    #[serde(default)]
+    pub group_ids: Vec<u64>,
+}
+
+impl ClassStudent {
+    pub fn name(&self) -> String {
+        let mut name = self.last_name.clone();
+        name.push(' ');
+        name.push_str(&self.first_name);
+        if let Some(middle_name) = &self.middle_name {
+            name.push(' ');
+            name.push_str(middle_name);
+        }
+        name
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IssuedMark {
+    /// Unique ID of this mark
+    pub id: u64,
+    /// Profile ID of the student who received this mark
+    pub student_profile_id: u64,
+    /// ID of the scheduled lesson this mark was issued on
+    pub schedule_lesson_id: Option<u64>,
+    /// ID of the subject group of the lesson
+    pub group_id: Option<u64>,
+    /// Value of this mark
+    #[serde(rename = "name")]
+    pub value: MarkValue,
+    /// Different grading system based values for this mark
+    #[serde(rename = "values")]
+    pub system_values: Vec<SystemBasedMarkValue>,
+    /// Extra comment for this mark
+    pub comment: Option<String>,
+    /// Weight index for this mark
+    pub weight: f32,
+    /// ID of the control form that belongs to this mark
+    pub control_form_id: Option<u64>,
+    /// Whether this mark is a control examination mark
+    pub is_exam: bool,
+    /// Whether this mark is a point, that should be corrected
+    pub is_point: bool,
+    /// Time at which this mark was created
+    #[serde(deserialize_with = "date_time::deserialize")]
+    pub created_at: NaiveDateTime,
+    /// Time at which this mark was updated
+    #[serde(deserialize_with = "date_time::deserialize")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// Class journal of a subject group: its lessons, students and marks for a period
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GroupJournal {
+    /// ID of the subject group of this journal
+    pub group_id: u64,
+    /// Lessons of the group in the period
+    pub lessons: Vec<TeacherScheduleItem>,
+    /// Students of the group
+    pub students: Vec<ClassStudent>,
+    /// Marks issued to the group in the period
+    pub marks: Vec<IssuedMark>,
+}
+
+impl GroupJournal {
+    /// All marks of the student with the provided profile ID
+    pub fn marks_of(&self, student_id: u64) -> Vec<&IssuedMark> {
+        self.marks
+            .iter()
+            .filter(|mark| mark.student_profile_id == student_id)
+            .collect()
+    }
+
+    /// All marks issued on the lesson with the provided ID
+    pub fn marks_on(&self, lesson_id: u64) -> Vec<&IssuedMark> {
+        self.marks
+            .iter()
+            .filter(|mark| mark.schedule_lesson_id == Some(lesson_id))
+            .collect()
+    }
+}
+
+/// Deserialization of the local date-times, which the API sends either in ISO 8601,
+/// with or without fractional seconds and an offset, or as `dd.mm.YYYY HH:MM`
+mod date_time {
+    use chrono::{DateTime, NaiveDateTime};
+    use serde::{de, Deserialize, Deserializer};
+
+    const FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S%.f", "%d.%m.%Y %H:%M"];
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveDateTime, D::Error> {
+        let text = String::deserialize(d)?;
+        if let Ok(time) = DateTime::parse_from_rfc3339(&text) {
+            return Ok(time.naive_local());
+        }
+        FORMATS
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(&text, format).ok())
+            .ok_or_else(|| de::Error::custom(format!("invalid date-time {:?}", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_time_formats() {
+        let times: Vec<NaiveDateTime> = [
+            "2022-10-12T08:30:00.000",
+            "2022-10-12T08:30:00+03:00",
+            "12.10.2022 08:30",
+        ]
+        .iter()
+        .map(|text| date_time::deserialize(serde_json::Value::from(*text)).unwrap())
+        .collect();
+        assert!(times.iter().all(|time| *time == times[0]));
+        assert!(date_time::deserialize(serde_json::Value::from("2022-10-12")).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+//! Offline mode: serving the last known responses from a local snapshot store
+//!
+//! Every successful `GET` response is saved to the store, namespaced by the account like
+//! the cache, except for the downloaded attachments. Depending on the [OfflineMode] the store is used only when the portal is
+//! unreachable, or instead of the network.
+
+use crate::cache::{CacheEntry, CacheStore, DiskCache, MemoryCache};
+use crate::error::{DnevnikError, Result};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// When the snapshot store is used instead of the network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OfflineMode {
+    /// Requests go to the network. Timeouts, connection errors and server errors
+    /// fall back to the snapshot
+    Fallback,
+    /// Requests never go to the network and are served from the snapshot only
+    Always,
+}
+
+/// Offline configuration of a diary: the snapshot store and its mode
+#[derive(Debug, Clone)]
+pub struct Offline {
+    store: Arc<dyn CacheStore>,
+    pub mode: OfflineMode,
+}
+
+impl Offline {
+    pub fn new<S: CacheStore + 'static>(store: S, mode: OfflineMode) -> Self {
+        Self {
+            store: Arc::new(store),
+            mode,
+        }
+    }
+
+    /// Snapshot kept in memory
+    pub fn memory(mode: OfflineMode) -> Self {
+        Self::new(MemoryCache::new(), mode)
+    }
+
+    /// Snapshot stored in the provided directory
+    pub fn disk<P: Into<PathBuf>>(dir: P, mode: OfflineMode) -> Self {
+        Self::new(DiskCache::new(dir), mode)
+    }
+
+    /// Same snapshot store with a different mode
+    pub fn with_mode(&self, mode: OfflineMode) -> Self {
+        Self {
+            store: self.store.clone(),
+            mode,
+        }
+    }
+
+    pub fn store(&self) -> &dyn CacheStore {
+        self.store.as_ref()
+    }
+
+    /// Saves the body received from the server at the provided time
+    pub(crate) async fn save(&self, key: &str, body: Bytes, fetched_at: DateTime<Utc>) {
+        let entry = CacheEntry {
+            body,
+            etag: None,
+            last_modified: None,
+            stored_at: fetched_at,
+        };
+        if let Err(e) = self.store.put(key, entry).await {
+            log::warn!("Could not save offline snapshot of {}: {}", key, e);
+        }
+    }
+
+    pub(crate) async fn load(&self, endpoint: &str, key: &str) -> Result<CacheEntry> {
+        self.store
+            .get(key)
+            .await?
+            .ok_or_else(|| DnevnikError::Offline {
+                endpoint: endpoint.to_string(),
+            })
+    }
+}
+
+/// Whether the error means that the portal is unreachable or failing
+pub(crate) fn is_outage(error: &DnevnikError) -> bool {
+    match error {
+        DnevnikError::Timeout { .. } | DnevnikError::Request { .. } => true,
+        DnevnikError::Status { status, .. } => status.is_server_error(),
+        _ => false,
+    }
+}
+
+/// Data returned by the diary along with its staleness
+#[derive(Debug, Clone)]
+pub struct Snapshot<T> {
+    pub data: T,
+    /// Sync time of the oldest response served from the offline snapshot.
+    /// `None` if all the data was received from the network
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+impl<T> Snapshot<T> {
+    /// Whether any of the data was served from the offline snapshot
+    pub fn is_stale(&self) -> bool {
+        self.synced_at.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::config::Endpoint;
+    use crate::diary::Diary;
+    use crate::model::hw::StudentHomework;
+    use crate::retry::RetryPolicy;
+    use crate::testing::{diary_with, fixture, profile_transport, TempDir};
+    use crate::transport::{HttpResponse, MemoryTransport};
+    use chrono::TimeZone;
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_offline_fallback() -> anyhow::Result<()> {
+        let schedule_path = "/mobile/api/schedule";
+        let transport = profile_transport()
+            .json(
+                schedule_path,
+                &fixture("get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json"),
+            )
+            .route(
+                Method::GET,
+                schedule_path,
+                HttpResponse::new(StatusCode::SERVICE_UNAVAILABLE, ""),
+            );
+        let offline = Offline::memory(OfflineMode::Fallback);
+        let diary = diary_with(transport)
+            .retry(RetryPolicy::none())
+            .offline(offline.clone())
+            .build()
+            .await?;
+        let date = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        let live = diary.snapshot(|diary| async move { diary.schedule(date).await });
+        assert!(!live.await?.is_stale());
+        // the portal is down now
+        let stale = diary
+            .snapshot(|diary| async move { diary.schedule(date).await })
+            .await?;
+        assert!(stale.is_stale());
+        assert_eq!(stale.data.lessons.len(), 7);
+
+        // the same snapshot works without any network access for the same account
+        let diary = diary_with(MemoryTransport::new())
+            .offline(offline.with_mode(OfflineMode::Always))
+            .build()
+            .await?;
+        assert_eq!(diary.schedule(date).await?.lessons.len(), 7);
+        let error = diary.homework(date, date).await.unwrap_err();
+        assert!(matches!(error, DnevnikError::Offline { .. }));
+
+        // but not to a client with another token
+        let other = Diary::builder("other")
+            .transport(MemoryTransport::new())
+            .offline(offline.with_mode(OfflineMode::Always))
+            .build()
+            .await;
+        assert!(matches!(other, Err(DnevnikError::Offline { .. })));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_offline_store_contents() -> anyhow::Result<()> {
+        let schedule = fixture("get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json");
+        let homeworks: Vec<StudentHomework> = serde_json::from_value(fixture(
+            "get_core_api_student_homeworks@begin_prepared_date=10.10.2022&end_prepared_date=24.10.2022&student_profile_id=1000001.json",
+        ))?;
+        let attachment = homeworks[0].homework_entry.attachments[0].clone();
+        let transport = profile_transport()
+            .json("/mobile/api/schedule", &schedule)
+            .route(
+                Method::GET,
+                attachment.relative_path.as_str(),
+                HttpResponse::new(StatusCode::OK, vec![0; attachment.file_size as usize]),
+            );
+        let cache = Cache::memory();
+        let offline = Offline::memory(OfflineMode::Fallback);
+        let diary = diary_with(transport)
+            .cache(cache.clone())
+            .offline(offline.clone())
+            .build()
+            .await?;
+        let date = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        diary.schedule(date).await?;
+        diary.schedule(date).await?;
+        // the response served from the cache keeps the time it was fetched at
+        let key = format!(
+            "{}?student_id=1000001&date=2022-10-12#1000001",
+            diary.config().endpoint_url(Endpoint::Schedule)
+        );
+        let cached = cache.store().get(&key).await?.unwrap();
+        let saved = offline.store().get(&key).await?.unwrap();
+        assert_eq!(saved.stored_at, cached.stored_at);
+
+        // attachments are not saved to the snapshot
+        let dir = TempDir::new("offline");
+        diary
+            .download_attachment(dir.path().join("task.txt"), &attachment)
+            .await?;
+        let attachment_key = format!(
+            "{}#1000001",
+            diary.config().resolve(&attachment.relative_path)
+        );
+        assert!(offline.store().get(&attachment_key).await?.is_none());
+        Ok(())
+    }
+}
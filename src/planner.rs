@@ -0,0 +1,258 @@
+//! Homework workload planning from the expected durations and due dates of the homework
+//!
+//! Homework is due on the day of the lesson it is prepared for, so it can be done on any
+//! day from its assignment until the day before that lesson.
+
+use crate::model::hw::StudentHomework;
+use crate::time::moscow;
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Expected time of the homework of a subject
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SubjectLoad {
+    pub subject_name: String,
+    /// Amount of homework entries
+    pub homeworks: usize,
+    pub minutes: u32,
+}
+
+/// Expected time of the homework due on a day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DayLoad {
+    /// Date the homework is due
+    pub date: NaiveDate,
+    pub minutes: u32,
+    /// Load by subject, sorted by the subject name
+    pub subjects: Vec<SubjectLoad>,
+    /// Whether the homework takes more than the daily budget
+    pub overloaded: bool,
+}
+
+/// Part of a homework to be done on a day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StudySession {
+    /// ID of the homework entry
+    pub homework_id: u64,
+    pub subject_name: String,
+    pub due_date: NaiveDate,
+    pub minutes: u32,
+}
+
+/// Sessions planned for a day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StudyDay {
+    pub date: NaiveDate,
+    pub minutes: u32,
+    pub sessions: Vec<StudySession>,
+}
+
+/// Homework spread over the days before it is due
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StudyPlan {
+    /// Days with planned sessions, in order
+    pub days: Vec<StudyDay>,
+    /// Parts of the homework that don't fit into the budget before they are due
+    pub unscheduled: Vec<StudySession>,
+}
+
+/// Builder of the homework workload and study plan
+#[derive(Debug, Clone)]
+pub struct WorkloadPlanner {
+    budget: u32,
+    start: NaiveDate,
+    include_ready: bool,
+}
+
+impl Default for WorkloadPlanner {
+    fn default() -> Self {
+        Self {
+            budget: 120,
+            start: Utc::now().with_timezone(&moscow()).date_naive(),
+            include_ready: false,
+        }
+    }
+}
+
+impl WorkloadPlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Minutes a day the student can spend on homework, 120 by default
+    pub fn budget(mut self, minutes: u32) -> Self {
+        self.budget = minutes;
+        self
+    }
+
+    /// First day of the plan, today by default
+    pub fn start(mut self, date: NaiveDate) -> Self {
+        self.start = date;
+        self
+    }
+
+    /// Whether the homework marked as ready is counted, `false` by default
+    pub fn include_ready(mut self, include_ready: bool) -> Self {
+        self.include_ready = include_ready;
+        self
+    }
+
+    /// Homework that is not deleted and has a due date
+    fn homework<'a>(
+        &'a self,
+        homeworks: &'a [StudentHomework],
+    ) -> impl Iterator<Item = (&'a StudentHomework, NaiveDate)> {
+        homeworks
+            .iter()
+            .filter(|homework| self.include_ready || !homework.is_ready)
+            .filter(|homework| homework.homework_entry.deleted_at.is_none())
+            .filter_map(|homework| Some((homework, homework.homework_entry.due_date()?)))
+    }
+
+    /// Expected load of the homework by due date
+    pub fn load(&self, homeworks: &[StudentHomework]) -> Vec<DayLoad> {
+        let mut days: BTreeMap<NaiveDate, BTreeMap<&str, SubjectLoad>> = BTreeMap::new();
+        for (homework, due_date) in self.homework(homeworks) {
+            let entry = &homework.homework_entry;
+            let subject = days
+                .entry(due_date)
+                .or_default()
+                .entry(&entry.subject().name)
+                .or_insert_with(|| SubjectLoad {
+                    subject_name: entry.subject().name.clone(),
+                    homeworks: 0,
+                    minutes: 0,
+                });
+            subject.homeworks += 1;
+            subject.minutes += entry.expected_duration;
+        }
+        days.into_iter()
+            .map(|(date, subjects)| {
+                let minutes = subjects.values().map(|subject| subject.minutes).sum();
+                DayLoad {
+                    date,
+                    minutes,
+                    subjects: subjects.into_values().collect(),
+                    overloaded: minutes > self.budget,
+                }
+            })
+            .collect()
+    }
+
+    /// Spreads the homework over the days before it is due, starting with the homework due
+    /// first. Each homework goes to the least loaded of its days, and is split across several
+    /// days if it doesn't fit into the remaining budget of one
+    pub fn plan(&self, homeworks: &[StudentHomework]) -> StudyPlan {
+        let mut pending: Vec<(&StudentHomework, NaiveDate)> = self.homework(homeworks).collect();
+        pending.sort_by_key(|(homework, due_date)| (*due_date, homework.homework_entry.id));
+
+        let mut plan = StudyPlan::default();
+        let mut days: BTreeMap<NaiveDate, StudyDay> = BTreeMap::new();
+        for (homework, due_date) in pending {
+            let entry = &homework.homework_entry;
+            let first = entry
+                .assigned_on()
+                .map_or(self.start, |assigned| assigned.max(self.start));
+            let session = |minutes| StudySession {
+                homework_id: entry.id,
+                subject_name: entry.subject().name.clone(),
+                due_date,
+                minutes,
+            };
+            let mut left = entry.expected_duration;
+            while left > 0 {
+                let free = |date: &NaiveDate| {
+                    let planned = days.get(date).map_or(0, |day| day.minutes);
+                    self.budget.saturating_sub(planned)
+                };
+                // the least loaded day, the earliest one among equally loaded days
+                let day = date_range(first, due_date)
+                    .filter(|date| free(date) > 0)
+                    .max_by_key(|date| (free(date), -date.num_days_from_ce()));
+                let date = match day {
+                    Some(date) => date,
+                    None => {
+                        plan.unscheduled.push(session(left));
+                        break;
+                    }
+                };
+                let minutes = left.min(free(&date));
+                let day = days.entry(date).or_insert(StudyDay {
+                    date,
+                    minutes: 0,
+                    sessions: vec![],
+                });
+                day.minutes += minutes;
+                day.sessions.push(session(minutes));
+                left -= minutes;
+            }
+        }
+        plan.days = days.into_values().collect();
+        plan
+    }
+}
+
+/// Days from `from` until the day before `until`
+fn date_range(from: NaiveDate, until: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    let days = (until - from).num_days().max(0);
+    (0..days).map(move |offset| from + Duration::days(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::fixture;
+
+    #[test]
+    fn test_workload_plan() {
+        let homeworks: Vec<StudentHomework> = serde_json::from_value(fixture(
+            "get_core_api_student_homeworks@begin_prepared_date=10.10.2022&end_prepared_date=24.10.2022&student_profile_id=1000001.json",
+        ))
+        .unwrap();
+        let day = |day| NaiveDate::from_ymd_opt(2022, 10, day).unwrap();
+        let planner = WorkloadPlanner::new().budget(45).start(day(10));
+
+        // the russian language homework is ready
+        let load = planner.load(&homeworks);
+        let minutes: Vec<(NaiveDate, u32, bool)> = load
+            .iter()
+            .map(|load| (load.date, load.minutes, load.overloaded))
+            .collect();
+        assert_eq!(
+            minutes,
+            [
+                (day(12), 30, false),
+                (day(14), 40, false),
+                (day(17), 60, true)
+            ]
+        );
+
+        // physics and history are assigned on the 12th, history is split to fit the budget
+        let plan = planner.plan(&homeworks);
+        let sessions: Vec<(NaiveDate, &str, u32)> = plan
+            .days
+            .iter()
+            .flat_map(|day| {
+                day.sessions
+                    .iter()
+                    .map(move |session| (day.date, session.subject_name.as_str(), session.minutes))
+            })
+            .collect();
+        assert_eq!(
+            sessions,
+            [
+                (day(10), "Алгебра", 30),
+                (day(12), "Физика", 40),
+                (day(13), "История", 45),
+                (day(14), "История", 15),
+            ]
+        );
+        assert!(plan.unscheduled.is_empty());
+
+        let late = WorkloadPlanner::new().budget(45).start(day(16));
+        let plan = late.plan(&homeworks);
+        assert_eq!(plan.unscheduled.len(), 3);
+        assert_eq!(plan.days[0].minutes, 45);
+    }
+}
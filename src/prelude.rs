@@ -1,9 +1,29 @@
 //! Module that exports most needed structures for this crate
-pub use crate::diary::Diary;
+pub use crate::analytics::attendance::{attendance_report, AttendanceReport};
+pub use crate::analytics::simulator::{HypotheticalMark, Simulator};
+pub use crate::analytics::{cross_check, weighted_average, AverageOptions};
+pub use crate::auth::{AuthToken, Authenticator, OtpChallenge};
+pub use crate::cache::{Cache, CacheStore, DiskCache, MemoryCache};
+pub use crate::config::{DiaryConfig, Endpoint};
+pub use crate::diary::{Diary, DiaryBuilder, RangeOptions};
+pub use crate::diff::{diff, DiaryEvent, DiarySnapshot};
+pub use crate::error::DnevnikError;
+pub use crate::ical::CalendarExport;
 pub use crate::model::attendance::{StudentAttendance, StudentVisit};
+pub use crate::model::grade::{GradeSystem, MarkValue};
 pub use crate::model::hw::{HomeworkAttachment, HomeworkEntry, HomeworkSubject, StudentHomework};
 pub use crate::model::lessons::{
     AcademicYear, LessonActivity, LessonInstance, Schedule, ScheduleActivity,
 };
 pub use crate::model::marks::{GlobalAverageGrade, LocalGradeMark, LocalGradeMarkValue};
-pub use crate::model::{Account, StudentDetails, StudentProfile};
+pub use crate::model::teacher::{ClassStudent, GroupJournal, IssuedMark, TeacherScheduleItem};
+pub use crate::model::{Account, ProfileType, StudentDetails, StudentProfile};
+pub use crate::offline::{Offline, OfflineMode, Snapshot};
+pub use crate::planner::{StudyPlan, WorkloadPlanner};
+pub use crate::retry::{RateLimit, RetryPolicy};
+pub use crate::teacher::TeacherDiary;
+pub use crate::token::{RefreshingToken, StaticToken, TokenProvider};
+pub use crate::transport::{
+    HttpRequest, HttpResponse, MemoryTransport, ReqwestTransport, Transport,
+};
+pub use crate::watch::{WatchConfig, Watcher};
@@ -0,0 +1,197 @@
+//! Retrying failed requests and limiting the request rate
+
+use crate::error::DnevnikError;
+use rand::Rng;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Describes which failed requests are retried and how long to wait between attempts
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum amount of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the second attempt, doubled for every next attempt
+    pub base_delay: Duration,
+    /// Upper bound for the delay between attempts
+    pub max_delay: Duration,
+    /// Whether the delay is randomized, so parallel clients don't retry at the same time
+    pub jitter: bool,
+    /// HTTP status codes that are considered temporary
+    pub retry_statuses: Vec<u16>,
+    /// Whether timed out requests are retried
+    pub retry_timeouts: bool,
+    /// Whether requests that failed to connect or receive the response are retried
+    pub retry_connection_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: true,
+            retry_statuses: vec![429, 500, 502, 503, 504],
+            retry_timeouts: true,
+            retry_connection_errors: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the request that failed with the provided error should be retried
+    pub fn is_retryable(&self, error: &DnevnikError) -> bool {
+        match error {
+            DnevnikError::Status { status, .. } => self.retry_statuses.contains(&status.as_u16()),
+            DnevnikError::Timeout { .. } => self.retry_timeouts,
+            DnevnikError::Request { .. } => self.retry_connection_errors,
+            _ => false,
+        }
+    }
+
+    /// Delay before the attempt following the provided one-based failed attempt
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        if self.jitter {
+            // half of the delay is fixed, the other half is random
+            let half = exponential / 2;
+            half + half.mul_f64(rand::thread_rng().gen::<f64>())
+        } else {
+            exponential
+        }
+    }
+}
+
+/// Lowest rate allowed by the [RateLimiter], one request a minute
+const MIN_RATE: f64 = 1.0 / 60.0;
+
+/// Rate of requests allowed by the [RateLimiter]
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Amount of requests allowed per second on average
+    pub requests_per_second: f64,
+    /// Amount of requests that may be sent at once after a period of inactivity
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// This limit with zero, negative and NaN rates raised to one request a minute
+    pub fn clamped(self) -> Self {
+        Self {
+            requests_per_second: self.requests_per_second.max(MIN_RATE),
+            ..self
+        }
+    }
+}
+
+/// Token bucket rate limiter, shared by all clones of a diary
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter with the [clamped](RateLimit::clamped) limit
+    pub fn new(limit: RateLimit) -> Self {
+        let limit = limit.clamped();
+        Self {
+            limit,
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+                bucket.tokens = (bucket.tokens + elapsed * self.limit.requests_per_second)
+                    .min(self.limit.burst.max(1) as f64);
+                bucket.refilled_at = now;
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.limit.requests_per_second)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_secs(2));
+        assert_eq!(policy.delay(10), Duration::from_secs(10));
+        let jittered = RetryPolicy::default().delay(2);
+        assert!(jittered >= Duration::from_millis(500) && jittered <= Duration::from_secs(1));
+        assert!(policy.is_retryable(&DnevnikError::from_status(
+            "/",
+            StatusCode::SERVICE_UNAVAILABLE,
+            b""
+        )));
+        assert!(!policy.is_retryable(&DnevnikError::from_status("/", StatusCode::NOT_FOUND, b"")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter() {
+        let limiter = RateLimiter::new(RateLimit {
+            requests_per_second: 2.0,
+            burst: 2,
+        });
+        let start = Instant::now();
+        for _ in 0..6 {
+            limiter.acquire().await;
+        }
+        // two requests pass at once, the other four need two seconds
+        assert!(start.elapsed() >= Duration::from_secs(2));
+        assert!(start.elapsed() < Duration::from_millis(2100));
+
+        for rate in [0.0, -1.0, f64::NAN] {
+            let limiter = RateLimiter::new(RateLimit {
+                requests_per_second: rate,
+                burst: 1,
+            });
+            let start = Instant::now();
+            limiter.acquire().await;
+            limiter.acquire().await;
+            // the second request waits for a minute instead of panicking
+            assert!(start.elapsed() >= Duration::from_secs(59));
+            assert!(start.elapsed() < Duration::from_secs(61));
+        }
+    }
+}
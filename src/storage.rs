@@ -0,0 +1,482 @@
+//! SQLite archive of the diary data, kept up to date by syncing with the [Diary]
+//!
+//! Tables mirror the [model](crate::model) types and can be queried with plain SQL through
+//! [Storage::connection]. Marks and homework are only overwritten when their `updated_at`
+//! is newer than the archived one, so history survives the portal dropping old years.
+
+use crate::diary::{Diary, RangeOptions};
+use crate::error::Result;
+use crate::model::attendance::{format_duration, StudentAttendance, TIME_FORMAT};
+use crate::model::hw::StudentHomework;
+use crate::model::lessons::{FinalMark, Schedule, ScheduleActivity};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS schedules (
+    student_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    PRIMARY KEY (student_id, date)
+);
+CREATE TABLE IF NOT EXISTS lessons (
+    schedule_id INTEGER NOT NULL,
+    student_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    subject_id INTEGER NOT NULL,
+    subject_name TEXT NOT NULL,
+    teacher TEXT NOT NULL,
+    begin_utc TEXT NOT NULL,
+    end_utc TEXT NOT NULL,
+    room TEXT NOT NULL,
+    homework TEXT NOT NULL,
+    is_cancelled INTEGER NOT NULL,
+    is_missed_lesson INTEGER NOT NULL,
+    is_virtual INTEGER NOT NULL,
+    PRIMARY KEY (student_id, schedule_id)
+);
+CREATE TABLE IF NOT EXISTS marks (
+    id INTEGER PRIMARY KEY,
+    student_id INTEGER NOT NULL,
+    schedule_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    subject_id INTEGER NOT NULL,
+    subject_name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    five REAL,
+    hundred REAL,
+    weight REAL NOT NULL,
+    comment TEXT,
+    cause TEXT NOT NULL,
+    is_exam INTEGER NOT NULL,
+    is_point INTEGER NOT NULL,
+    point_date TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS homeworks (
+    id INTEGER PRIMARY KEY,
+    student_id INTEGER NOT NULL,
+    entry_id INTEGER NOT NULL,
+    subject_id INTEGER NOT NULL,
+    subject_name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    expected_duration INTEGER NOT NULL,
+    is_ready INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    deleted_at TEXT
+);
+CREATE TABLE IF NOT EXISTS visits (
+    student_id INTEGER NOT NULL,
+    date TEXT NOT NULL,
+    entrance TEXT NOT NULL,
+    exit TEXT NOT NULL,
+    duration TEXT NOT NULL,
+    address TEXT NOT NULL,
+    visit_type TEXT NOT NULL,
+    is_warning INTEGER NOT NULL,
+    short_name TEXT NOT NULL,
+    PRIMARY KEY (student_id, date, entrance)
+);
+CREATE TABLE IF NOT EXISTS final_marks (
+    student_id INTEGER NOT NULL,
+    academic_year_id INTEGER NOT NULL,
+    subject_id INTEGER NOT NULL,
+    subject_name TEXT NOT NULL,
+    value REAL NOT NULL,
+    grade_system TEXT NOT NULL,
+    attested INTEGER NOT NULL,
+    has_debt INTEGER NOT NULL,
+    PRIMARY KEY (student_id, academic_year_id, subject_id)
+);
+CREATE TABLE IF NOT EXISTS sync_state (
+    student_id INTEGER PRIMARY KEY,
+    synced_to TEXT NOT NULL,
+    synced_at TEXT NOT NULL
+);
+";
+
+/// Days before the end of the previous sync that are synced again by
+/// [Storage::sync_incremental], since recent marks and homework are often edited
+const RESYNC_DAYS: i64 = 14;
+
+/// Amounts of rows changed by a sync
+#[derive(Debug, Clone, Default)]
+pub struct SyncReport {
+    /// Days whose schedule was archived
+    pub days: usize,
+    /// Days whose schedule could not be fetched
+    pub failed_days: Vec<NaiveDate>,
+    /// New or updated marks
+    pub marks: usize,
+    /// New or updated homework
+    pub homeworks: usize,
+    /// New or changed visits
+    pub visits: usize,
+    /// New or changed final marks
+    pub final_marks: usize,
+    /// Academic years whose final marks could not be fetched
+    pub failed_years: Vec<u16>,
+}
+
+/// SQLite archive of the diary data
+#[derive(Debug)]
+pub struct Storage {
+    connection: Mutex<Connection>,
+}
+
+impl Storage {
+    /// Opens or creates the database at the provided path
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Creates a database kept in memory
+    pub fn memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Connection to the database, for querying the archive with SQL
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap()
+    }
+
+    /// Last day synced for the student, if any
+    pub fn synced_to(&self, student_id: u64) -> Result<Option<NaiveDate>> {
+        Ok(self
+            .connection()
+            .query_row(
+                "SELECT synced_to FROM sync_state WHERE student_id = ?1",
+                [student_id],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Pulls data of the diary's student between `from` and `to` and archives it
+    pub async fn sync(
+        &self,
+        diary: &Diary,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<SyncReport> {
+        let student_id = diary.student_id();
+        let schedules = diary
+            .schedule_range(from, to, RangeOptions::default())
+            .await;
+        let homeworks = diary.homework(from, to).await?;
+        let visits = match diary.child().and_then(|child| child.contract_id) {
+            Some(_) => diary.visits(from, to).await?,
+            None => vec![],
+        };
+        let mut report = SyncReport::default();
+        let mut final_marks = vec![];
+        for year in diary.academic_years().await? {
+            match diary.final_marks(&year).await {
+                Ok(marks) => final_marks.push((year.id, marks)),
+                Err(e) => {
+                    log::warn!("Could not sync final marks of {}: {}", year.description, e);
+                    report.failed_years.push(year.id);
+                }
+            }
+        }
+
+        let mut connection = self.connection();
+        let tx = connection.transaction()?;
+        for (date, schedule) in schedules {
+            match schedule {
+                Ok(schedule) => {
+                    report.marks += archive_schedule(&tx, student_id, &schedule)?;
+                    report.days += 1;
+                }
+                Err(e) => {
+                    log::warn!("Could not sync schedule of {}: {}", date, e);
+                    report.failed_days.push(date);
+                }
+            }
+        }
+        for homework in &homeworks {
+            report.homeworks += archive_homework(&tx, student_id, homework)?;
+        }
+        for attendance in &visits {
+            report.visits += archive_visits(&tx, student_id, attendance)?;
+        }
+        for (year_id, marks) in &final_marks {
+            for mark in marks {
+                report.final_marks += archive_final_mark(&tx, student_id, *year_id, mark)?;
+            }
+        }
+        tx.execute(
+            "INSERT INTO sync_state (student_id, synced_to, synced_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (student_id) DO UPDATE SET
+                synced_to = MAX(synced_to, excluded.synced_to), synced_at = excluded.synced_at",
+            params![student_id, to.date_naive(), Utc::now()],
+        )?;
+        tx.commit()?;
+        Ok(report)
+    }
+
+    /// Syncs from a little before the end of the previous sync up to `to`.
+    /// The first sync starts at the beginning of the current academic year
+    pub async fn sync_incremental(&self, diary: &Diary, to: DateTime<Utc>) -> Result<SyncReport> {
+        let from = match self.synced_to(diary.student_id())? {
+            Some(date) => date - Duration::days(RESYNC_DAYS),
+            None => diary
+                .academic_years()
+                .await?
+                .into_iter()
+                .find(|year| year.is_current)
+                .map(|year| year.begin_date)
+                .unwrap_or_else(|| to.date_naive()),
+        };
+        let from = Utc.from_utc_datetime(&from.and_hms_opt(12, 0, 0).unwrap());
+        self.sync(diary, from, to).await
+    }
+}
+
+fn archive_schedule(tx: &Connection, student_id: u64, schedule: &Schedule) -> Result<usize> {
+    tx.execute(
+        "INSERT OR REPLACE INTO schedules (student_id, date, summary) VALUES (?1, ?2, ?3)",
+        params![student_id, schedule.date, schedule.summary],
+    )?;
+    let mut marks = 0;
+    for activity in &schedule.lessons {
+        let activity = match activity {
+            ScheduleActivity::Lesson(lesson) => lesson,
+            ScheduleActivity::Break(_) => continue,
+        };
+        let lesson = &activity.subject;
+        tx.execute(
+            "INSERT OR REPLACE INTO lessons (schedule_id, student_id, date, subject_id, subject_name,
+                teacher, begin_utc, end_utc, room, homework, is_cancelled, is_missed_lesson, is_virtual)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                lesson.schedule_id,
+                student_id,
+                schedule.date,
+                lesson.subject_id,
+                lesson.subject_name,
+                lesson.teacher.name(),
+                activity.begin,
+                activity.end,
+                activity.room,
+                lesson.homework,
+                lesson.is_cancelled,
+                lesson.is_missed_lesson,
+                lesson.is_virtual,
+            ],
+        )?;
+        for mark in &lesson.marks {
+            let grade = mark.grade();
+            marks += tx.execute(
+                "INSERT INTO marks (id, student_id, schedule_id, date, subject_id, subject_name,
+                    value, five, hundred, weight, comment, cause, is_exam, is_point, point_date,
+                    created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+                 ON CONFLICT (id) DO UPDATE SET
+                    value = excluded.value, five = excluded.five, hundred = excluded.hundred,
+                    weight = excluded.weight, comment = excluded.comment, cause = excluded.cause,
+                    is_exam = excluded.is_exam, is_point = excluded.is_point,
+                    point_date = excluded.point_date, updated_at = excluded.updated_at
+                 WHERE excluded.updated_at > marks.updated_at",
+                params![
+                    mark.id,
+                    student_id,
+                    lesson.schedule_id,
+                    schedule.date,
+                    lesson.subject_id,
+                    lesson.subject_name,
+                    mark.value.to_string(),
+                    grade.map(|grade| grade.five_based),
+                    grade.map(|grade| grade.hundred_based),
+                    mark.weight,
+                    mark.comment,
+                    mark.cause,
+                    mark.is_exam,
+                    mark.is_point,
+                    mark.point_date,
+                    mark.created_at,
+                    mark.updated_at,
+                ],
+            )?;
+        }
+    }
+    Ok(marks)
+}
+
+fn archive_homework(tx: &Connection, student_id: u64, homework: &StudentHomework) -> Result<usize> {
+    let entry = &homework.homework_entry;
+    Ok(tx.execute(
+        "INSERT INTO homeworks (id, student_id, entry_id, subject_id, subject_name, description,
+            expected_duration, is_ready, created_at, updated_at, deleted_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+         ON CONFLICT (id) DO UPDATE SET
+            description = excluded.description, expected_duration = excluded.expected_duration,
+            is_ready = excluded.is_ready, updated_at = excluded.updated_at,
+            deleted_at = excluded.deleted_at
+         WHERE excluded.updated_at > homeworks.updated_at
+            OR excluded.is_ready != homeworks.is_ready",
+        params![
+            homework.id,
+            student_id,
+            entry.id,
+            entry.subject().id,
+            entry.subject().name,
+            entry.description,
+            entry.expected_duration,
+            homework.is_ready,
+            entry.created_at,
+            entry.updated_at,
+            entry.deleted_at,
+        ],
+    )?)
+}
+
+fn archive_visits(
+    tx: &Connection,
+    student_id: u64,
+    attendance: &StudentAttendance,
+) -> Result<usize> {
+    let mut changed = 0;
+    for visit in &attendance.visits {
+        changed += tx.execute(
+            "INSERT INTO visits (student_id, date, entrance, exit, duration, address, visit_type,
+                is_warning, short_name)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (student_id, date, entrance) DO UPDATE SET
+                exit = excluded.exit, duration = excluded.duration, is_warning = excluded.is_warning
+             WHERE excluded.exit != visits.exit",
+            params![
+                student_id,
+                attendance.date,
+                visit.entrance.format(TIME_FORMAT).to_string(),
+                visit.exit.map_or_else(
+                    || "-".to_string(),
+                    |exit| exit.format(TIME_FORMAT).to_string()
+                ),
+                visit.duration.map(format_duration).unwrap_or_default(),
+                visit.address,
+                visit.visit_type,
+                visit.is_warning,
+                visit.short_name,
+            ],
+        )?;
+    }
+    Ok(changed)
+}
+
+fn archive_final_mark(
+    tx: &Connection,
+    student_id: u64,
+    year_id: u16,
+    mark: &FinalMark,
+) -> Result<usize> {
+    Ok(tx.execute(
+        "INSERT INTO final_marks (student_id, academic_year_id, subject_id, subject_name, value,
+            grade_system, attested, has_debt)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT (student_id, academic_year_id, subject_id) DO UPDATE SET
+            value = excluded.value, attested = excluded.attested, has_debt = excluded.has_debt
+         WHERE excluded.value != final_marks.value
+            OR excluded.attested != final_marks.attested
+            OR excluded.has_debt != final_marks.has_debt",
+        params![
+            student_id,
+            year_id,
+            mark.subject_id,
+            mark.subject_name,
+            mark.value,
+            mark.grade_system.as_str(),
+            mark.attested,
+            mark.has_debt,
+        ],
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{diary_with, fixture, profile_transport};
+    use crate::transport::HttpResponse;
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_sync_upserts() -> anyhow::Result<()> {
+        let transport = profile_transport()
+            .json(
+                "/mobile/api/schedule",
+                &fixture("get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json"),
+            )
+            .json(
+                "/core/api/student_homeworks",
+                &fixture("get_core_api_student_homeworks@begin_prepared_date=10.10.2022&end_prepared_date=24.10.2022&student_profile_id=1000001.json"),
+            )
+            .json(
+                "/mobile/api/visits",
+                &fixture("get_mobile_api_visits@contract_id=2000001&from=2022-10-05&to=2022-10-12.json"),
+            )
+            .json(
+                "/core/api/academic_years",
+                &fixture("get_core_api_academic_years.json"),
+            )
+            // the student was not enrolled in the first year
+            .route(
+                Method::GET,
+                "/core/api/final_marks_prev_year",
+                HttpResponse::new(StatusCode::NOT_FOUND, ""),
+            )
+            .json(
+                "/core/api/final_marks_prev_year",
+                &fixture("get_core_api_final_marks_prev_year@academic_year_id=4&is_year_mark=true&student_profile_id=1000001.json"),
+            );
+        let diary = diary_with(transport).build().await?;
+        let storage = Storage::memory()?;
+        let day = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+
+        let first = storage.sync(&diary, day, day).await?;
+        assert_eq!(first.days, 1);
+        assert_eq!(first.marks, 2);
+        assert_eq!(first.homeworks, 4);
+        assert!(first.visits > 0);
+        assert!(first.final_marks > 0);
+        assert_eq!(first.failed_years.len(), 1);
+        // unchanged data is not rewritten
+        let second = storage.sync(&diary, day, day).await?;
+        assert_eq!(second.marks + second.homeworks + second.visits, 0);
+        assert!(second.failed_years.is_empty());
+
+        // a sibling in the same class keeps their own lessons
+        let schedule: Schedule = serde_json::from_value(fixture(
+            "get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json",
+        ))?;
+        archive_schedule(&storage.connection(), 1000003, &schedule)?;
+        let students: i64 = storage.connection().query_row(
+            "SELECT COUNT(DISTINCT student_id) FROM lessons",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(students, 2);
+
+        let average: f64 = storage.connection().query_row(
+            "SELECT AVG(five) FROM marks WHERE student_id = ?1",
+            [diary.student_id()],
+            |row| row.get(0),
+        )?;
+        assert_eq!(average, 4.5);
+        assert_eq!(
+            storage.synced_to(diary.student_id())?,
+            Some(day.date_naive())
+        );
+        Ok(())
+    }
+}
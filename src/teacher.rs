@@ -0,0 +1,220 @@
+//! Teacher-side access to the diary: own schedule, class journals, students and issued marks
+
+use crate::auth::AuthToken;
+use crate::client::Api;
+use crate::config::{DiaryConfig, Endpoint};
+use crate::diary::DiaryBuilder;
+use crate::error::Result;
+use crate::model::teacher::{ClassStudent, GroupJournal, IssuedMark, TeacherScheduleItem};
+use crate::model::{ProfileType, StudentProfile, SubjectGroup};
+use crate::transport::HttpRequest;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug, Clone)]
+pub struct TeacherDiary {
+    api: Api,
+    pub profile: StudentProfile,
+    teacher_id: u64,
+}
+
+impl TeacherDiary {
+    pub(crate) fn new(api: Api, profile: StudentProfile) -> Self {
+        Self {
+            api,
+            teacher_id: profile.account.id,
+            profile,
+        }
+    }
+
+    /// Creates a teacher-side client with default configuration.
+    /// Use [DiaryBuilder::build_teacher] for custom configuration
+    pub async fn connect<S: Into<String>>(token: S) -> Result<Self> {
+        DiaryBuilder::new(token).build_teacher().await
+    }
+
+    /// Configuration of this diary
+    pub fn config(&self) -> &DiaryConfig {
+        &self.api.config
+    }
+
+    /// Token that will be used for the next request, along with its expiry
+    pub async fn token(&self) -> Result<AuthToken> {
+        self.api.tokens.token().await
+    }
+
+    /// ID of the teacher whose data is requested by this diary
+    pub fn teacher_id(&self) -> u64 {
+        self.teacher_id
+    }
+
+    fn request(&self, endpoint: Endpoint) -> Result<HttpRequest> {
+        Ok(HttpRequest::get(self.api.endpoint(endpoint)?)
+            .header("Profile-Type", ProfileType::Teacher.as_str()))
+    }
+
+    async fn fetch<T: DeserializeOwned>(&self, request: HttpRequest) -> Result<T> {
+        self.api.fetch(request).await
+    }
+
+    /// Lessons taught by this teacher in the provided period
+    pub async fn schedule(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<TeacherScheduleItem>> {
+        self.fetch(
+            self.request(Endpoint::ScheduleItems)?
+                .query(&[("teacher_id", self.teacher_id)])
+                .query(&[("from", from.date_naive().to_string())])
+                .query(&[("to", to.date_naive().to_string())]),
+        )
+        .await
+    }
+
+    /// Subject groups taught by this teacher
+    pub async fn groups(&self) -> Result<Vec<SubjectGroup>> {
+        self.fetch(
+            self.request(Endpoint::Groups)?
+                .query(&[("teacher_id", self.teacher_id)]),
+        )
+        .await
+    }
+
+    /// Students of the class unit with the provided ID
+    pub async fn class_students(&self, class_id: u64) -> Result<Vec<ClassStudent>> {
+        self.fetch(
+            self.request(Endpoint::StudentProfiles)?
+                .query(&[("class_unit_ids", class_id)]),
+        )
+        .await
+    }
+
+    /// Students of the subject group with the provided ID
+    pub async fn group_students(&self, group_id: u64) -> Result<Vec<ClassStudent>> {
+        self.fetch(
+            self.request(Endpoint::StudentProfiles)?
+                .query(&[("group_ids", group_id)]),
+        )
+        .await
+    }
+
+    /// Marks issued by this teacher in the provided period
+    pub async fn issued_marks(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<IssuedMark>> {
+        self.fetch(
+            self.request(Endpoint::Marks)?
+                .query(&[("teacher_id", self.teacher_id)])
+                .query(&[("created_at_from", from.format("%d.%m.%Y").to_string())])
+                .query(&[("created_at_to", to.format("%d.%m.%Y").to_string())]),
+        )
+        .await
+    }
+
+    /// Class journal of the group: its lessons, students and marks in the provided period
+    pub async fn journal(
+        &self,
+        group: &SubjectGroup,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<GroupJournal> {
+        let lessons = self.fetch(
+            self.request(Endpoint::ScheduleItems)?
+                .query(&[("group_id", group.id)])
+                .query(&[("from", from.date_naive().to_string())])
+                .query(&[("to", to.date_naive().to_string())]),
+        );
+        let students = self.group_students(group.id);
+        let marks = self.fetch(
+            self.request(Endpoint::Marks)?
+                .query(&[("group_ids", group.id)])
+                .query(&[("created_at_from", from.format("%d.%m.%Y").to_string())])
+                .query(&[("created_at_to", to.format("%d.%m.%Y").to_string())]),
+        );
+        let (lessons, students, marks) = tokio::try_join!(lessons, students, marks)?;
+        Ok(GroupJournal {
+            group_id: group.id,
+            lessons,
+            students,
+            marks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::DnevnikError;
+    use crate::model::grade::MarkValue;
+    use crate::testing::{diary_with, fixture, profile_transport};
+    use crate::transport::MemoryTransport;
+    use chrono::{NaiveDate, NaiveTime, TimeZone};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_teacher_journal() -> anyhow::Result<()> {
+        let mut profile = fixture("get_mobile_api_profile.json");
+        profile["profile"]["id"] = 4000001.into();
+        profile["profile"]["type"] = "teacher".into();
+        let transport = Arc::new(
+            MemoryTransport::new()
+                .json("/mobile/api/profile", &profile)
+                .json(
+                    "/core/api/groups",
+                    &fixture("get_core_api_groups@teacher_id=4000001.json"),
+                )
+                .json(
+                    "/core/api/schedule_items",
+                    &fixture("get_core_api_schedule_items@from=2022-10-12&group_id=8001&to=2022-10-12.json"),
+                )
+                .json(
+                    "/core/api/student_profiles",
+                    &fixture("get_core_api_student_profiles@group_ids=8001.json"),
+                )
+                .json(
+                    "/core/api/marks",
+                    &fixture("get_core_api_marks@created_at_from=12.10.2022&created_at_to=12.10.2022&group_ids=8001.json"),
+                ),
+        );
+        let teacher = diary_with(transport.clone()).build_teacher().await?;
+        assert_eq!(teacher.teacher_id(), 4000001);
+
+        let groups = teacher.groups().await?;
+        assert_eq!(groups.len(), 2);
+        let date = Utc.with_ymd_and_hms(2022, 10, 12, 12, 0, 0).unwrap();
+        let journal = teacher.journal(&groups[0], date, date).await?;
+        assert_eq!(journal.lessons.len(), 2);
+        let lesson = &journal.lessons[0];
+        assert_eq!(
+            lesson.date(),
+            NaiveDate::from_ymd_opt(2022, 10, 12).unwrap()
+        );
+        assert_eq!(
+            lesson.date_time.time(),
+            NaiveTime::from_hms_opt(8, 30, 0).unwrap()
+        );
+        assert!(journal.lessons[1].is_cancelled);
+        assert_eq!(journal.students[0].name(), "Фамилия Имя Отчество");
+        assert_eq!(journal.marks_of(1000004).len(), 1);
+        let marks = journal.marks_on(5101);
+        assert_eq!(marks[0].value, MarkValue::Five(5.0));
+        assert_eq!(
+            marks[1].updated_at.time(),
+            NaiveTime::from_hms_opt(10, 2, 0).unwrap()
+        );
+        assert!(transport.requests()[1..]
+            .iter()
+            .all(|request| request.headers["Profile-Type"] == "teacher"));
+
+        // student accounts are rejected
+        let error = diary_with(profile_transport())
+            .build_teacher()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, DnevnikError::WrongProfileType { .. }));
+        Ok(())
+    }
+}
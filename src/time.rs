@@ -0,0 +1,11 @@
+//! Time zone of the diary
+
+use chrono::FixedOffset;
+
+/// Moscow has been UTC+3 all year round since 2014
+const MOSCOW_OFFSET: i32 = 3 * 3600;
+
+/// Offset of the time zone the diary works in
+pub(crate) fn moscow() -> FixedOffset {
+    FixedOffset::east_opt(MOSCOW_OFFSET).unwrap()
+}
@@ -0,0 +1,217 @@
+//! Sources of auth tokens consulted by the [Diary](crate::diary::Diary) before each request
+
+use crate::auth::AuthToken;
+use crate::client::parse_url;
+use crate::config::{DiaryConfig, Endpoint};
+use crate::error::{DnevnikError, Result};
+use crate::model::StudentSession;
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use reqwest::header::HeaderValue;
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+
+/// Tokens expiring sooner than this are refreshed before being used
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// Provides auth tokens for the diary requests
+#[async_trait]
+pub trait TokenProvider: Debug + Send + Sync {
+    /// Token that should be used for the next request
+    async fn token(&self) -> Result<AuthToken>;
+
+    /// Called when the server rejected the `stale` token. Returns the token to retry the request with
+    async fn refresh(&self, stale: &AuthToken) -> Result<AuthToken>;
+}
+
+/// Token that never changes. Requests fail with [DnevnikError::Unauthorized] once it expires
+#[derive(Debug, Clone)]
+pub struct StaticToken(pub AuthToken);
+
+#[async_trait]
+impl TokenProvider for StaticToken {
+    async fn token(&self) -> Result<AuthToken> {
+        Ok(self.0.clone())
+    }
+
+    async fn refresh(&self, _stale: &AuthToken) -> Result<AuthToken> {
+        Err(DnevnikError::Authentication {
+            reason: "static token can not be refreshed".to_string(),
+        })
+    }
+}
+
+type RefreshFuture = Pin<Box<dyn Future<Output = Result<AuthToken>> + Send>>;
+type RefreshCallback = dyn Fn(AuthToken) -> RefreshFuture + Send + Sync;
+
+/// Token that is refreshed with a callback when it expires or gets rejected by the server
+pub struct RefreshingToken {
+    current: RwLock<AuthToken>,
+    refresh_lock: tokio::sync::Mutex<()>,
+    callback: Box<RefreshCallback>,
+}
+
+impl RefreshingToken {
+    /// Creates a token refreshed by the provided callback, which receives the stale token
+    pub fn new<F, Fut>(initial: AuthToken, callback: F) -> Self
+    where
+        F: Fn(AuthToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<AuthToken>> + Send + 'static,
+    {
+        Self {
+            current: RwLock::new(initial),
+            refresh_lock: tokio::sync::Mutex::new(()),
+            callback: Box::new(move |stale| Box::pin(callback(stale))),
+        }
+    }
+
+    /// Creates a token that is refreshed through the sessions endpoint of the provided host
+    pub fn session(initial: AuthToken, config: &DiaryConfig) -> Result<Self> {
+        let transport = ReqwestTransport::new(config.timeout)?;
+        Self::session_with(initial, config, Arc::new(transport))
+    }
+
+    /// Same as [session](RefreshingToken::session), but sends requests through the provided transport
+    pub fn session_with(
+        initial: AuthToken,
+        config: &DiaryConfig,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self> {
+        let url = parse_url(&config.endpoint_url(Endpoint::Sessions))?;
+        Ok(Self::new(initial, move |stale: AuthToken| {
+            let transport = transport.clone();
+            let url = url.clone();
+            async move {
+                let endpoint = url.path().to_string();
+                let mut request = HttpRequest::post(url)
+                    .json(&serde_json::json!({ "auth_token": stale.token }))?;
+                request
+                    .headers
+                    .insert("Auth-Token", HeaderValue::from_str(&stale.token)?);
+                let response = transport.send(request).await?;
+                if !response.status.is_success() {
+                    return Err(DnevnikError::from_status(
+                        &endpoint,
+                        response.status,
+                        &response.body,
+                    ));
+                }
+                let session: StudentSession = serde_json::from_slice(&response.body)
+                    .map_err(|e| DnevnikError::decode(&endpoint, &response.body, e))?;
+                session
+                    .authentication_token
+                    .map(AuthToken::new)
+                    .ok_or_else(|| DnevnikError::missing("authentication_token", "session"))
+            }
+        }))
+    }
+
+    fn current(&self) -> AuthToken {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl Debug for RefreshingToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RefreshingToken")
+            .field("expires_at", &self.current().expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TokenProvider for RefreshingToken {
+    async fn token(&self) -> Result<AuthToken> {
+        let current = self.current();
+        let expires_soon = current
+            .expires_at
+            .map(|expiry| expiry - Duration::seconds(EXPIRY_MARGIN_SECS) <= Utc::now())
+            .unwrap_or(false);
+        if expires_soon {
+            self.refresh(&current).await
+        } else {
+            Ok(current)
+        }
+    }
+
+    async fn refresh(&self, stale: &AuthToken) -> Result<AuthToken> {
+        let _guard = self.refresh_lock.lock().await;
+        // another request might have refreshed the token while we were waiting
+        let current = self.current();
+        if current.token != stale.token {
+            return Ok(current);
+        }
+        let fresh = (self.callback)(current).await?;
+        *self.current.write().unwrap() = fresh.clone();
+        Ok(fresh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{HttpResponse, MemoryTransport};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_refresh_once() -> anyhow::Result<()> {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let provider = RefreshingToken::new(AuthToken::new("old"), move |_| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(AuthToken::new("new"))
+            }
+        });
+        let stale = provider.token().await?;
+        assert_eq!(stale.token, "old");
+        assert_eq!(provider.refresh(&stale).await?.token, "new");
+        // refreshing with an already replaced token does not call the callback again
+        assert_eq!(provider.refresh(&stale).await?.token, "new");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_refreshed_proactively() -> anyhow::Result<()> {
+        let expired = AuthToken {
+            token: "old".to_string(),
+            expires_at: Some(Utc::now() - Duration::minutes(5)),
+        };
+        let provider = RefreshingToken::new(expired, |_| async { Ok(AuthToken::new("new")) });
+        assert_eq!(provider.token().await?.token, "new");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_refresh() -> anyhow::Result<()> {
+        let transport = Arc::new(MemoryTransport::new().route(
+            reqwest::Method::POST,
+            "/lms/api/sessions",
+            HttpResponse::json(&serde_json::json!({
+                "id": 1,
+                "person_id": "00000000-0000-0000-0000-000000000000",
+                "last_name": "", "first_name": "", "middle_name": "",
+                "phone_number": "", "email": "", "snils": "",
+                "authentication_token": "renewed"
+            }))?,
+        ));
+        let provider = RefreshingToken::session_with(
+            AuthToken::new("old"),
+            &DiaryConfig::default(),
+            transport.clone(),
+        )?;
+        assert_eq!(
+            provider.refresh(&AuthToken::new("old")).await?.token,
+            "renewed"
+        );
+        let body: serde_json::Value =
+            serde_json::from_slice(transport.requests()[0].body.as_ref().unwrap())?;
+        assert_eq!(body["auth_token"], "old");
+        Ok(())
+    }
+}
@@ -0,0 +1,269 @@
+//! HTTP transports used by the diary to send requests
+
+use crate::error::{DnevnikError, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderValue, IntoHeaderName, CONTENT_TYPE};
+use reqwest::{Client, ClientBuilder, Method, StatusCode, Url};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Debug;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Request sent through a [Transport]
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: HeaderMap,
+    pub body: Option<Bytes>,
+    /// Timeout overriding the default timeout of the transport
+    pub timeout: Option<Duration>,
+}
+
+impl HttpRequest {
+    pub fn new(method: Method, url: Url) -> Self {
+        Self {
+            method,
+            url,
+            headers: HeaderMap::new(),
+            body: None,
+            timeout: None,
+        }
+    }
+
+    pub fn get(url: Url) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub fn post(url: Url) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    /// Appends the provided pairs to the query string
+    pub fn query<T: ToString>(mut self, pairs: &[(&str, T)]) -> Self {
+        {
+            let mut query = self.url.query_pairs_mut();
+            for (key, value) in pairs {
+                query.append_pair(key, &value.to_string());
+            }
+        }
+        self
+    }
+
+    pub fn header<K: IntoHeaderName>(mut self, key: K, value: &'static str) -> Self {
+        self.headers.insert(key, HeaderValue::from_static(value));
+        self
+    }
+
+    /// Sets the body to the JSON representation of the value
+    pub fn json<T: Serialize>(mut self, value: &T) -> Result<Self> {
+        let body = serde_json::to_vec(value)
+            .map_err(|e| DnevnikError::encode(format!("body of {}", self.endpoint()), e))?;
+        self.headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self.body = Some(Bytes::from(body));
+        Ok(self)
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Path of the requested URL, used to describe the endpoint in errors
+    pub fn endpoint(&self) -> String {
+        self.url.path().to_string()
+    }
+}
+
+/// Response received from a [Transport]
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl HttpResponse {
+    pub fn new<B: Into<Bytes>>(status: StatusCode, body: B) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    /// Successful response with the JSON representation of the value
+    pub fn json<T: Serialize>(value: &T) -> Result<Self> {
+        let body =
+            serde_json::to_vec(value).map_err(|e| DnevnikError::encode("response body", e))?;
+        let mut response = Self::new(StatusCode::OK, body);
+        response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(response)
+    }
+}
+
+/// Sends HTTP requests. Non-successful status codes are returned as responses, not as errors
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse>;
+}
+
+#[async_trait]
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        (**self).send(request).await
+    }
+}
+
+/// Transport backed by the [reqwest] client
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// Creates a transport with the provided default timeout
+    pub fn new(timeout: Duration) -> Result<Self> {
+        ClientBuilder::new()
+            .timeout(timeout)
+            .build()
+            .map(Self::with_client)
+            .map_err(DnevnikError::Client)
+    }
+
+    /// Creates a transport from a preconfigured client, e.g. with a proxy or custom TLS
+    pub fn with_client(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Transport for ReqwestTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let endpoint = request.endpoint();
+        let mut builder = self
+            .client
+            .request(request.method, request.url)
+            .headers(request.headers);
+        if let Some(body) = request.body {
+            builder = builder.body(body);
+        }
+        if let Some(timeout) = request.timeout {
+            builder = builder.timeout(timeout);
+        }
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| DnevnikError::from_request(&endpoint, e))?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| DnevnikError::from_request(&endpoint, e))?;
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// In-memory transport serving predefined responses, for tests
+///
+/// Responses are matched by method and URL path. When several responses are
+/// registered for the same route they are served in order, and the last one is repeated.
+/// Requests to unknown routes get a 404 response.
+#[derive(Debug, Default)]
+pub struct MemoryTransport {
+    routes: Mutex<HashMap<(Method, String), VecDeque<HttpResponse>>>,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a response for the provided method and path
+    pub fn route<P: Into<String>>(self, method: Method, path: P, response: HttpResponse) -> Self {
+        self.routes
+            .lock()
+            .unwrap()
+            .entry((method, path.into()))
+            .or_default()
+            .push_back(response);
+        self
+    }
+
+    /// Registers a successful JSON response for a `GET` request
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value can't be serialized
+    pub fn json<P: Into<String>, T: Serialize>(self, path: P, value: &T) -> Self {
+        let response = HttpResponse::json(value).expect("response value is not serializable");
+        self.route(Method::GET, path, response)
+    }
+
+    /// All requests sent through this transport
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn send(&self, request: HttpRequest) -> Result<HttpResponse> {
+        let key = (request.method.clone(), request.url.path().to_string());
+        self.requests.lock().unwrap().push(request);
+        let mut routes = self.routes.lock().unwrap();
+        let response = match routes.get_mut(&key) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        };
+        Ok(response.unwrap_or_else(|| HttpResponse::new(StatusCode::NOT_FOUND, "")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_transport() -> anyhow::Result<()> {
+        let transport = MemoryTransport::new()
+            .route(
+                Method::GET,
+                "/years",
+                HttpResponse::new(StatusCode::BAD_GATEWAY, ""),
+            )
+            .json("/years", &serde_json::json!([]));
+        let url = Url::parse("https://dnevnik.mos.ru/years")?;
+        let request = HttpRequest::get(url.clone()).query(&[("student_id", 1)]);
+        assert_eq!(request.url.query(), Some("student_id=1"));
+        let first = transport.send(request.clone()).await?;
+        let second = transport.send(request.clone()).await?;
+        let third = transport.send(request).await?;
+        assert_eq!(first.status, StatusCode::BAD_GATEWAY);
+        assert_eq!(second.body, third.body);
+        assert_eq!(transport.requests().len(), 3);
+        let missing = transport
+            .send(HttpRequest::get(Url::parse("https://dnevnik.mos.ru/none")?))
+            .await?;
+        assert_eq!(missing.status, StatusCode::NOT_FOUND);
+
+        // maps with non-string keys can't be sent as JSON
+        let body = HashMap::from([(vec![1u8], 1)]);
+        let error = HttpRequest::post(url).json(&body).unwrap_err();
+        assert!(matches!(error, DnevnikError::Encode { .. }));
+        assert!(HttpResponse::json(&body).is_err());
+        Ok(())
+    }
+}
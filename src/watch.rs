@@ -0,0 +1,312 @@
+//! Polling the diary for changes and exposing them as a [Stream] of [DiaryEvent]s
+//!
+//! Sources are polled only when the consumer asks for more events, so a slow consumer
+//! slows the polling down instead of buffering events without bound.
+
+use crate::diary::Diary;
+use crate::diff::{diff, diff_progress, diff_visits, DiaryEvent, DiarySnapshot};
+use crate::error::{DnevnikError, Result};
+use crate::model::attendance::StudentAttendance;
+use crate::model::hw::StudentHomework;
+use crate::model::lessons::Schedule;
+use crate::model::marks::GlobalAverageGrade;
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, Utc};
+use futures::stream::{self, Stream};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Polling intervals of the watched sources. `None` disables polling of the source
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    pub schedule: Option<Duration>,
+    pub homework: Option<Duration>,
+    pub progress: Option<Duration>,
+    pub visits: Option<Duration>,
+    /// Random deviation of the intervals, as a fraction of the interval
+    pub jitter: f64,
+    /// Days before today included in the polled schedule, homework and visits
+    pub days_back: i64,
+    /// Days after today included in the polled schedule and homework
+    pub days_ahead: i64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            schedule: Some(Duration::from_secs(15 * 60)),
+            homework: Some(Duration::from_secs(30 * 60)),
+            progress: Some(Duration::from_secs(60 * 60)),
+            visits: Some(Duration::from_secs(60 * 60)),
+            jitter: 0.1,
+            days_back: 7,
+            days_ahead: 7,
+        }
+    }
+}
+
+/// Last seen state of the watched sources, persisted between restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchState {
+    pub schedules: Option<Vec<Schedule>>,
+    pub homeworks: Option<Vec<StudentHomework>>,
+    /// First and last due dates of the last polled homework
+    pub homework_period: Option<(NaiveDate, NaiveDate)>,
+    pub progress: Option<Vec<GlobalAverageGrade>>,
+    pub visits: Option<Vec<StudentAttendance>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Schedule,
+    Homework,
+    Progress,
+    Visits,
+}
+
+/// Polls the diary and reports the changes since the last poll
+#[derive(Debug)]
+pub struct Watcher {
+    diary: Diary,
+    config: WatchConfig,
+    state: WatchState,
+    state_path: Option<PathBuf>,
+    due: Vec<(Source, Instant)>,
+    pending: VecDeque<DiaryEvent>,
+}
+
+impl Watcher {
+    /// Creates a watcher polling all enabled sources right away. The first poll of
+    /// each source only records the current state, unless a previous state was loaded.
+    /// Visits are not polled for students without a contract
+    pub fn new(diary: Diary, config: WatchConfig) -> Self {
+        let now = Instant::now();
+        let visits = match diary.child().and_then(|child| child.contract_id) {
+            Some(_) => config.visits,
+            None => None,
+        };
+        let due = [
+            (Source::Schedule, config.schedule),
+            (Source::Homework, config.homework),
+            (Source::Progress, config.progress),
+            (Source::Visits, visits),
+        ]
+        .into_iter()
+        .filter_map(|(source, interval)| interval.map(|_| (source, now)))
+        .collect();
+        Self {
+            diary,
+            config,
+            state: WatchState::default(),
+            state_path: None,
+            due,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Persists the state to the provided file after every poll, loading it first if it exists
+    pub async fn state_file<P: Into<PathBuf>>(mut self, path: P) -> Result<Self> {
+        let path = path.into();
+        match tokio::fs::read(&path).await {
+            Ok(contents) => {
+                self.state = serde_json::from_slice(&contents)
+                    .map_err(|e| DnevnikError::decode(&path.to_string_lossy(), &contents, e))?;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.state_path = Some(path);
+        Ok(self)
+    }
+
+    /// Last seen state of the watched sources
+    pub fn state(&self) -> &WatchState {
+        &self.state
+    }
+
+    /// Stream of the changes. Failed polls are yielded as errors and retried on the next interval
+    pub fn into_stream(self) -> impl Stream<Item = Result<DiaryEvent>> {
+        stream::unfold(self, |mut watcher| async move {
+            loop {
+                if let Some(event) = watcher.pending.pop_front() {
+                    return Some((Ok(event), watcher));
+                }
+                let index = (0..watcher.due.len()).min_by_key(|index| watcher.due[*index].1)?;
+                let (source, at) = watcher.due[index];
+                tokio::time::sleep_until(at).await;
+                watcher.due[index].1 = Instant::now() + watcher.interval(source);
+                match watcher.poll(source).await {
+                    Ok(events) => watcher.pending.extend(events),
+                    Err(error) => return Some((Err(error), watcher)),
+                }
+            }
+        })
+    }
+
+    fn interval(&self, source: Source) -> Duration {
+        let interval = match source {
+            Source::Schedule => self.config.schedule,
+            Source::Homework => self.config.homework,
+            Source::Progress => self.config.progress,
+            Source::Visits => self.config.visits,
+        }
+        .unwrap_or_default();
+        let jitter = self.config.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return interval;
+        }
+        interval.mul_f64(rand::thread_rng().gen_range(1.0 - jitter..=1.0 + jitter))
+    }
+
+    fn window(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let now = Utc::now();
+        (
+            now - ChronoDuration::days(self.config.days_back),
+            now + ChronoDuration::days(self.config.days_ahead),
+        )
+    }
+
+    async fn poll(&mut self, source: Source) -> Result<Vec<DiaryEvent>> {
+        let (from, to) = self.window();
+        let events = match source {
+            Source::Schedule => {
+                let (mut new, failed) =
+                    DiarySnapshot::fetch_schedules(&self.diary, from, to).await?;
+                // days that could not be fetched keep their last seen schedule
+                if let Some(old) = &self.state.schedules {
+                    new.extend(
+                        old.iter()
+                            .filter(|schedule| failed.contains(&schedule.date))
+                            .cloned(),
+                    );
+                }
+                let old = self.state.schedules.replace(new.clone());
+                old.map(|old| {
+                    diff(
+                        &DiarySnapshot::new(old, vec![]),
+                        &DiarySnapshot::new(new, vec![]),
+                    )
+                })
+                .unwrap_or_default()
+            }
+            Source::Homework => {
+                let new = self.diary.homework(from, to).await?;
+                let period = (from.date_naive(), to.date_naive());
+                let old = self.state.homeworks.replace(new.clone());
+                let old_period = self.state.homework_period.replace(period);
+                old.map(|old| {
+                    let mut old = DiarySnapshot::new(vec![], old);
+                    old.homework_period = old_period;
+                    diff(
+                        &old,
+                        &DiarySnapshot::new(vec![], new).homework_period(period.0, period.1),
+                    )
+                })
+                .unwrap_or_default()
+            }
+            Source::Progress => {
+                let new = self.diary.progress().await?;
+                let old = self.state.progress.replace(new.clone());
+                old.map(|old| diff_progress(&old, &new)).unwrap_or_default()
+            }
+            Source::Visits => {
+                let new = self.diary.visits(from, Utc::now()).await?;
+                let old = self.state.visits.replace(new.clone());
+                old.map(|old| diff_visits(&old, &new)).unwrap_or_default()
+            }
+        };
+        self.save().await?;
+        Ok(events)
+    }
+
+    async fn save(&self) -> Result<()> {
+        if let Some(path) = &self.state_path {
+            let contents = serde_json::to_vec(&self.state)
+                .map_err(|e| DnevnikError::encode(path.to_string_lossy(), e))?;
+            tokio::fs::write(path, contents).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::lessons::ScheduleActivity;
+    use crate::testing::{diary_with, fixture, profile_transport, TempDir};
+    use crate::transport::{HttpResponse, MemoryTransport};
+    use futures::StreamExt;
+    use reqwest::{Method, StatusCode};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_new_mark() -> anyhow::Result<()> {
+        let old = fixture("get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json");
+        let mut schedule: Schedule = serde_json::from_value(old.clone())?;
+        if let ScheduleActivity::Lesson(lesson) = &mut schedule.lessons[0] {
+            let mut mark = lesson.subject.marks.first().cloned().unwrap();
+            mark.id = 9100;
+            lesson.subject.marks.push(mark);
+        }
+        let transport = profile_transport()
+            .json("/mobile/api/schedule", &old)
+            .json("/mobile/api/schedule", &schedule);
+        let diary = diary_with(transport).build().await?;
+        let config = WatchConfig {
+            schedule: Some(Duration::from_secs(60)),
+            homework: None,
+            progress: None,
+            visits: None,
+            days_back: 0,
+            days_ahead: 0,
+            ..Default::default()
+        };
+        let dir = TempDir::new("watch");
+        let path = dir.path().join("state.json");
+        let watcher = Watcher::new(diary, config).state_file(&path).await?;
+        let mut events = Box::pin(watcher.into_stream());
+        match events.next().await.unwrap()? {
+            DiaryEvent::NewMark { mark, .. } => assert_eq!(mark.id, 9100),
+            other => panic!("unexpected event {:?}", other),
+        }
+        let state: WatchState = serde_json::from_slice(&tokio::fs::read(&path).await?)?;
+        assert_eq!(state.schedules.unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_partial_failures() -> anyhow::Result<()> {
+        let mut profile = fixture("get_mobile_api_profile.json");
+        profile["children"][0]["contract_id"] = serde_json::Value::Null;
+        let transport = MemoryTransport::new()
+            .json("/mobile/api/profile", &profile)
+            .route(
+                Method::GET,
+                "/mobile/api/schedule",
+                HttpResponse::new(StatusCode::NOT_FOUND, ""),
+            )
+            .json(
+                "/mobile/api/schedule",
+                &fixture("get_mobile_api_schedule@date=2022-10-12&student_id=1000001.json"),
+            );
+        let diary = diary_with(transport).build().await?;
+        let config = WatchConfig {
+            days_back: 1,
+            days_ahead: 0,
+            ..Default::default()
+        };
+        let mut watcher = Watcher::new(diary, config);
+        // the student has no contract to request the visits with
+        assert!(watcher
+            .due
+            .iter()
+            .all(|(source, _)| *source != Source::Visits));
+
+        // the day that failed does not discard the other one
+        watcher.poll(Source::Schedule).await?;
+        assert_eq!(watcher.state().schedules.as_ref().unwrap().len(), 1);
+        Ok(())
+    }
+}
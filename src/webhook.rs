@@ -0,0 +1,400 @@
+//! Delivery of diary events to webhooks as signed JSON
+//!
+//! Every delivery is a `POST` with the JSON body `{"id", "created_at", "event"}` and the
+//! `X-Dnevnik-Signature: sha256=<hex>` header holding the HMAC-SHA256 of the body,
+//! keyed with the secret of the webhook. Failed deliveries are queued with the delays of
+//! the [RetryPolicy]. The dispatcher has no background task: call
+//! [WebhookDispatcher::retry_pending] periodically, or [WebhookDispatcher::flush] to wait
+//! for the queue, otherwise queued deliveries are never retried.
+
+use crate::client::parse_url;
+use crate::diff::DiaryEvent;
+use crate::error::{DnevnikError, Result};
+use crate::retry::RetryPolicy;
+use crate::transport::{HttpRequest, ReqwestTransport, Transport};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+pub const SIGNATURE_HEADER: &str = "X-Dnevnik-Signature";
+pub const EVENT_HEADER: &str = "X-Dnevnik-Event";
+pub const DELIVERY_HEADER: &str = "X-Dnevnik-Delivery";
+/// Default amount of delivery attempts kept in the log
+const LOG_CAPACITY: usize = 1000;
+
+/// Webhook receiving the diary events
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub url: String,
+    secret: String,
+    /// Kinds of the delivered events, see [DiaryEvent::kind]. Empty means all events
+    pub events: Vec<String>,
+}
+
+impl Webhook {
+    pub fn new<U: Into<String>, S: Into<String>>(url: U, secret: S) -> Self {
+        Self {
+            url: url.into(),
+            secret: secret.into(),
+            events: vec![],
+        }
+    }
+
+    /// Limits the delivered events to the provided kinds
+    pub fn events<I, S>(mut self, kinds: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.events = kinds.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn accepts(&self, event: &DiaryEvent) -> bool {
+        self.events.is_empty() || self.events.iter().any(|kind| kind == event.kind())
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of the body
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        })
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    id: Uuid,
+    created_at: DateTime<Utc>,
+    event: &'a DiaryEvent,
+}
+
+/// Result of a single delivery attempt
+#[derive(Debug, Clone)]
+pub struct DeliveryRecord {
+    /// ID of the delivery, the same for all attempts
+    pub id: Uuid,
+    pub url: String,
+    pub event_kind: &'static str,
+    /// One-based number of the attempt
+    pub attempt: u32,
+    /// Status code of the response, if it was received
+    pub status: Option<u16>,
+    /// Description of the failure, `None` if the delivery succeeded
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+impl DeliveryRecord {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[derive(Debug)]
+struct PendingDelivery {
+    id: Uuid,
+    webhook: Webhook,
+    event_kind: &'static str,
+    body: Bytes,
+    attempt: u32,
+    due: Instant,
+}
+
+/// Sends diary events to the configured webhooks
+#[derive(Debug)]
+pub struct WebhookDispatcher {
+    transport: Arc<dyn Transport>,
+    webhooks: Vec<Webhook>,
+    retry: RetryPolicy,
+    queue: Mutex<VecDeque<PendingDelivery>>,
+    log: Mutex<VecDeque<DeliveryRecord>>,
+    log_capacity: usize,
+}
+
+impl WebhookDispatcher {
+    /// Creates a dispatcher sending requests with the [ReqwestTransport]
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_transport(ReqwestTransport::new(
+            Duration::from_secs(10),
+        )?))
+    }
+
+    pub fn with_transport<T: Transport + 'static>(transport: T) -> Self {
+        Self {
+            transport: Arc::new(transport),
+            webhooks: vec![],
+            retry: RetryPolicy {
+                max_attempts: 5,
+                base_delay: Duration::from_secs(5),
+                max_delay: Duration::from_secs(5 * 60),
+                ..Default::default()
+            },
+            queue: Mutex::new(VecDeque::new()),
+            log: Mutex::new(VecDeque::new()),
+            log_capacity: LOG_CAPACITY,
+        }
+    }
+
+    pub fn webhook(mut self, webhook: Webhook) -> Self {
+        self.webhooks.push(webhook);
+        self
+    }
+
+    /// Sets the amount of attempts and delays between them. All failures are retried
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Sets the amount of the latest delivery attempts kept in the log, 1000 by default
+    pub fn log_capacity(mut self, capacity: usize) -> Self {
+        self.log_capacity = capacity;
+        self
+    }
+
+    /// Delivers the event to all webhooks accepting it, queueing failed deliveries.
+    /// Returns the amount of successful deliveries.
+    ///
+    /// Queued deliveries are not retried by this call, see [Self::retry_pending]
+    pub async fn dispatch(&self, event: &DiaryEvent) -> Result<usize> {
+        let id = Uuid::new_v4();
+        let body = serde_json::to_vec(&Payload {
+            id,
+            created_at: Utc::now(),
+            event,
+        })
+        .map_err(|e| DnevnikError::encode(format!("{} event", event.kind()), e))?;
+        let body = Bytes::from(body);
+        let mut delivered = 0;
+        for webhook in self
+            .webhooks
+            .iter()
+            .filter(|webhook| webhook.accepts(event))
+        {
+            let delivery = PendingDelivery {
+                id,
+                webhook: webhook.clone(),
+                event_kind: event.kind(),
+                body: body.clone(),
+                attempt: 1,
+                due: Instant::now(),
+            };
+            if self.deliver(delivery).await {
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Retries the queued deliveries whose delay has passed. Returns the amount of successful
+    /// deliveries. Has to be called periodically, e.g. after every poll of the diary
+    pub async fn retry_pending(&self) -> usize {
+        let now = Instant::now();
+        let due: VecDeque<PendingDelivery> = {
+            let mut queue = self.queue.lock().unwrap();
+            let (due, waiting) = queue.drain(..).partition(|delivery| delivery.due <= now);
+            *queue = waiting;
+            due
+        };
+        let mut delivered = 0;
+        for delivery in due {
+            if self.deliver(delivery).await {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+
+    /// Waits for the queued deliveries, retrying each when its delay passes, until all of
+    /// them either succeed or run out of attempts. Returns the amount of successful deliveries
+    pub async fn flush(&self) -> usize {
+        let mut delivered = 0;
+        loop {
+            let next = self
+                .queue
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|delivery| delivery.due)
+                .min();
+            match next {
+                Some(due) => {
+                    tokio::time::sleep_until(due).await;
+                    delivered += self.retry_pending().await;
+                }
+                None => return delivered,
+            }
+        }
+    }
+
+    /// Amount of deliveries waiting for a retry
+    pub fn pending(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Latest delivery attempts, up to the capacity of the log
+    pub fn log(&self) -> Vec<DeliveryRecord> {
+        self.log.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes and returns the logged delivery attempts
+    pub fn drain_log(&self) -> Vec<DeliveryRecord> {
+        self.log.lock().unwrap().drain(..).collect()
+    }
+
+    async fn deliver(&self, mut delivery: PendingDelivery) -> bool {
+        let (status, error, retryable) = match self.send(&delivery).await {
+            Ok(status) if (200..300).contains(&status) => (Some(status), None, false),
+            Ok(status) => (Some(status), Some(format!("HTTP {}", status)), true),
+            // an invalid URL never becomes valid
+            Err(e @ DnevnikError::InvalidUrl { .. }) => (None, Some(e.to_string()), false),
+            Err(e) => (None, Some(e.to_string()), true),
+        };
+        let success = error.is_none();
+        match &error {
+            Some(error) if retryable => log::warn!(
+                "Delivery {} to {} failed: {}",
+                delivery.id,
+                delivery.webhook.url,
+                error
+            ),
+            Some(error) => log::error!(
+                "Delivery {} to {} failed permanently: {}",
+                delivery.id,
+                delivery.webhook.url,
+                error
+            ),
+            None => {}
+        }
+        let mut log = self.log.lock().unwrap();
+        if self.log_capacity > 0 {
+            while log.len() >= self.log_capacity {
+                log.pop_front();
+            }
+            log.push_back(DeliveryRecord {
+                id: delivery.id,
+                url: delivery.webhook.url.clone(),
+                event_kind: delivery.event_kind,
+                attempt: delivery.attempt,
+                status,
+                error,
+                at: Utc::now(),
+            });
+        }
+        drop(log);
+        if retryable && delivery.attempt < self.retry.max_attempts {
+            delivery.due = Instant::now() + self.retry.delay(delivery.attempt);
+            delivery.attempt += 1;
+            self.queue.lock().unwrap().push_back(delivery);
+        }
+        success
+    }
+
+    async fn send(&self, delivery: &PendingDelivery) -> Result<u16> {
+        let mut request = HttpRequest::post(parse_url(&delivery.webhook.url)?);
+        let signature = format!("sha256={}", sign(&delivery.webhook.secret, &delivery.body));
+        let headers = &mut request.headers;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&signature)?);
+        headers.insert(EVENT_HEADER, HeaderValue::from_static(delivery.event_kind));
+        headers.insert(
+            DELIVERY_HEADER,
+            HeaderValue::from_str(&delivery.id.to_string())?,
+        );
+        request.body = Some(delivery.body.clone());
+        let response = self.transport.send(request).await?;
+        Ok(response.status.as_u16())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::LessonRef;
+    use crate::testing::{MockResponse, MockServer};
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_signed_delivery_with_retry() -> anyhow::Result<()> {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let server = MockServer::start(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => MockResponse::new(500, "down"),
+            _ => MockResponse::new(204, ""),
+        })
+        .await;
+        let dispatcher =
+            WebhookDispatcher::with_transport(ReqwestTransport::new(Duration::from_secs(5))?)
+                .webhook(Webhook::new(server.url("/hooks/diary"), "s3cret"))
+                .webhook(Webhook::new(server.url("/hooks/marks"), "other").events(["new_mark"]))
+                .retry(RetryPolicy {
+                    base_delay: Duration::ZERO,
+                    ..Default::default()
+                });
+        let event = DiaryEvent::LessonCancelled {
+            lesson: LessonRef {
+                schedule_id: 5004,
+                date: NaiveDate::from_ymd_opt(2022, 10, 12).unwrap(),
+                subject_name: "История".to_string(),
+                begin: Utc::now(),
+            },
+        };
+
+        assert_eq!(dispatcher.dispatch(&event).await?, 0);
+        assert_eq!(dispatcher.pending(), 1);
+        assert_eq!(dispatcher.flush().await, 1);
+        assert_eq!(dispatcher.pending(), 0);
+
+        let requests = server.requests();
+        // the webhook for marks did not receive the cancellation
+        assert_eq!(requests.len(), 2);
+        let request = &requests[1];
+        assert_eq!(request.path(), "/hooks/diary");
+        assert_eq!(request.header("x-dnevnik-event"), Some("lesson_cancelled"));
+        assert_eq!(
+            request.header("x-dnevnik-signature").unwrap(),
+            format!("sha256={}", sign("s3cret", &request.body))
+        );
+        let log = dispatcher.log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].status, Some(500));
+        assert!(log[1].is_success() && log[1].attempt == 2);
+        assert_eq!(log[0].id, log[1].id);
+
+        // only the latest attempts are kept
+        let dispatcher = dispatcher.log_capacity(1);
+        dispatcher.dispatch(&event).await?;
+        dispatcher.dispatch(&event).await?;
+        assert_eq!(dispatcher.drain_log().len(), 1);
+        assert!(dispatcher.log().is_empty());
+
+        // deliveries to invalid URLs are not retried
+        let dispatcher = dispatcher.webhook(Webhook::new("not a url", "s3cret"));
+        dispatcher.dispatch(&event).await?;
+        assert_eq!(dispatcher.pending(), 0);
+        assert!(dispatcher.log()[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("invalid URL"));
+        Ok(())
+    }
+}